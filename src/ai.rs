@@ -1,171 +1,931 @@
+use crate::bitboard::square;
 use crate::board::Board;
-use crate::pieces::{Piece, PieceColor, PieceType};
+use crate::book::OpeningBook;
+use crate::eval::evaluate;
+use crate::moves::Move;
+use crate::pieces::{PieceColor, PieceType};
+use crate::syzygy::{CorruptTable, Tablebases, Wdl};
+use crate::transposition::{Bound, TranspositionTable};
 use rand::seq::SliceRandom;
+use rand::Rng;
 use std::cmp;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
-// Profundidad de búsqueda
-const MAX_DEPTH: i32 = 4;
+// Profundidad máxima de la profundización iterativa
+pub const MAX_DEPTH: i32 = 64;
+// Distancia máxima a la raíz, contando las extensiones y la búsqueda de quietud
+const MAX_PLY: i32 = 128;
+// Puntuación fuera de cualquier evaluación posible; se puede negar sin desbordar
+const INFINITY: i32 = MATE_SCORE + 1;
+// Media anchura inicial de la ventana de aspiración (un cuarto de peón)
+const ASPIRATION_WINDOW: i32 = 25;
+// Jugadas que se buscan a profundidad completa antes de empezar a reducir
+const LMR_MIN_MOVES: usize = 3;
+// Margen para no perder por tiempo por la latencia de la comunicación
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+// Jugadas que se suponen pendientes si no se indica "movestogo"
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+// Poda delta: una captura que ni ganando esto de más alcanza la cota no se mira (dos peones)
+const DELTA_MARGIN: i32 = 200;
+// Victoria segura según las tablas de finales: por encima de cualquier evaluación y por debajo
+// de los mates, que siguen siendo mejores
+const TB_WIN: i32 = MATE_THRESHOLD - 1 - MAX_PLY;
 
-// Valores básicos de piezas
+// Valores básicos de piezas en centipeones, para ordenar capturas y para la poda delta
+// (la evaluación usa los suyos, distintos en el medio juego y el final)
 fn get_piece_value(piece_type: PieceType) -> i32 {
     match piece_type {
-        PieceType::Pawn => 10,  // Valor del Peón
-        PieceType::Knight => 30,// Valor del Caballo
-        PieceType::Bishop => 30,// Valor del Alfil
-        PieceType::Rook => 50,  // Valor de la Torre
-        PieceType::Queen => 90, // Valor de la Reina
-        PieceType::King => 900, // Valor del Rey
+        PieceType::Pawn => 100,   // Valor del Peón
+        PieceType::Knight => 320, // Valor del Caballo
+        PieceType::Bishop => 330, // Valor del Alfil
+        PieceType::Rook => 500,   // Valor de la Torre
+        PieceType::Queen => 900,  // Valor de la Reina
+        PieceType::King => 1000,  // Solo como atacante: sus capturas se prueban las últimas
     }
 }
 
-pub fn get_best_move(board: &Board, color: PieceColor) -> Option<((usize, usize), (usize, usize))> {
-    let mut best_move = None;
-    let mut best_value = i32::MIN;
-    let mut alpha = i32::MIN;
-    let beta = i32::MAX;
-
-    // Obtener todos los movimientos posibles para todas las piezas de este color
-    let mut all_moves = Vec::new();
-    for y in 0..8 {
-        for x in 0..8 {
-            if let Some(piece) = board.grid[y][x] {
-                if piece.color == color {
-                    let moves = board.get_valid_moves((x, y));
-                    for dest in moves {
-                        all_moves.push(((x, y), dest));
-                    }
-                }
-            }
+// Información de cada iteración completada de la búsqueda (para las líneas "info" de UCI)
+pub struct SearchInfo {
+    pub depth: i32,
+    pub score: i32, // Desde el punto de vista del bando que mueve en la raíz
+    pub nodes: u64,
+    pub elapsed: Duration,
+    pub pv: Vec<Move>, // Variante principal
+    pub hashfull: usize, // Ocupación de la tabla de transposiciones, en tantos por mil
+    pub tbhits: u64, // Posiciones resueltas con las tablas de finales
+    pub tb_errors: Vec<CorruptTable>, // Archivos de tablas dañados encontrados desde la iteración anterior
+}
+
+// Límites de una búsqueda, con los mismos nombres que la orden "go" de UCI.
+// Los que valen None no se aplican; sin ninguno se busca hasta MAX_DEPTH o hasta `stop`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchLimits {
+    pub depth: Option<i32>,
+    pub movetime: Option<Duration>, // Tiempo fijo para esta jugada
+    pub nodes: Option<u64>,
+    // Reloj de cada bando e incremento por jugada
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    pub movestogo: Option<u32>,
+    pub infinite: bool, // Ignora el tiempo: solo para con `stop` (o con `depth`/`nodes`)
+    // Desempata al azar las jugadas de la raíz igual de prometedoras, para variar las partidas.
+    // No es un límite, pero acompaña a cada búsqueda; UCI lo deja desactivado.
+    pub random_ties: bool,
+    // Para los niveles de dificultad (UCI los deja a 0): ruido máximo que se suma a la evaluación
+    // y pérdida máxima, en centipeones, de la jugada elegida respecto a la mejor
+    pub eval_noise: i32,
+    pub move_margin: i32,
+}
+
+impl SearchLimits {
+    // Sin límites de profundidad, nodos ni tiempo para el bando que mueve
+    pub fn is_unbounded(&self, color: PieceColor) -> bool {
+        self.depth.is_none() && self.nodes.is_none() && (self.infinite || self.time_limits(color).is_none())
+    }
+
+    // Tiempo previsto (no se empieza otra iteración pasada su mitad) y tiempo máximo de la jugada
+    fn time_limits(&self, color: PieceColor) -> Option<(Duration, Duration)> {
+        if self.infinite {
+            return None;
+        }
+        if let Some(movetime) = self.movetime {
+            return Some((movetime, movetime));
+        }
+
+        let (time, increment) = match color {
+            PieceColor::White => (self.wtime?, self.winc.unwrap_or_default()),
+            PieceColor::Black => (self.btime?, self.binc.unwrap_or_default()),
+        };
+        // Una parte del reloj más la mitad del incremento, pudiendo alargarse hasta el triple
+        let moves_to_go = self.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let available = time.saturating_sub(MOVE_OVERHEAD);
+        let maximum = (time / moves_to_go * 3 + increment).min(available);
+        let optimum = (time / moves_to_go + increment / 2).min(maximum);
+        Some((optimum, maximum))
+    }
+}
+
+// Niveles de dificultad de la IA en la interfaz gráfica
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Difficulty {
+    Beginner,
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+    Maximum,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 5] = [
+        Difficulty::Beginner,
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Hard,
+        Difficulty::Maximum,
+    ];
+
+    // Los niveles bajos buscan poco, ven la posición con algo de ruido y eligen entre las jugadas
+    // que no pierden más del margen: juegan peor de forma constante, sin regalar piezas al azar
+    pub fn limits(self) -> SearchLimits {
+        let (depth, millis, eval_noise, move_margin) = match self {
+            Difficulty::Beginner => (Some(1), 300, 120, 150),
+            Difficulty::Easy => (Some(2), 500, 60, 80),
+            Difficulty::Medium => (Some(4), 1000, 25, 30),
+            Difficulty::Hard => (Some(8), 1500, 0, 0),
+            Difficulty::Maximum => (None, 3000, 0, 0),
+        };
+        SearchLimits {
+            depth,
+            movetime: Some(Duration::from_millis(millis)),
+            random_ties: true,
+            eval_noise,
+            move_margin,
+            ..SearchLimits::default()
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Difficulty::Beginner => "Principiante",
+            Difficulty::Easy => "Fácil",
+            Difficulty::Medium => "Normal",
+            Difficulty::Hard => "Difícil",
+            Difficulty::Maximum => "Máximo",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Puntuación de un mate en la raíz; un mate a `ply` medios movimientos vale MATE_SCORE - ply
+pub const MATE_SCORE: i32 = 99_999;
+// Las puntuaciones más allá de este umbral son mates
+pub const MATE_THRESHOLD: i32 = MATE_SCORE - 1_000;
+
+// `history` son los hashes de la partida hasta la posición actual incluida.
+// Si la posición está en el libro de aperturas se juega de él sin buscar.
+// Si se activa `stop` se devuelve la mejor jugada encontrada hasta entonces.
+// Junto a la jugada van los archivos de tablas de finales dañados que se han encontrado.
+pub fn get_best_move(
+    board: &Board,
+    history: &[u64],
+    difficulty: Difficulty,
+    book: Option<&OpeningBook>,
+    tablebases: Option<&Tablebases>,
+    tt: &mut TranspositionTable,
+    stop: &AtomicBool,
+) -> (Option<Move>, Vec<CorruptTable>) {
+    if let Some(book) = book
+        && let Some(mv) = book.probe(&mut board.clone(), history.len().saturating_sub(1)) {
+        return (Some(mv), Vec::new());
+    }
+    let mut tb_errors = Vec::new();
+    let best = search(board, history, &difficulty.limits(), tablebases, tt, stop, |info| {
+        tb_errors.extend_from_slice(&info.tb_errors);
+    });
+    (best, tb_errors)
+}
+
+// Profundización iterativa dentro de `limits`, o hasta que se active `stop`.
+// Si una iteración se interrumpe, se devuelve la mejor jugada de la última completa.
+// La tabla de transposiciones se conserva entre búsquedas de la misma partida.
+// Con `tablebases`, en los finales que cubren solo se buscan las jugadas que mejor resultado dan.
+pub fn search(
+    board: &Board,
+    history: &[u64],
+    limits: &SearchLimits,
+    tablebases: Option<&Tablebases>,
+    tt: &mut TranspositionTable,
+    stop: &AtomicBool,
+    mut on_iteration: impl FnMut(&SearchInfo),
+) -> Option<Move> {
+    let start = Instant::now();
+    let time_limits = limits.time_limits(board.side_to_move);
+    let max_depth = limits.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH);
+
+    // Una sola copia: la búsqueda hace y deshace las jugadas sobre ella
+    let mut board = board.clone();
+    tt.new_search();
+    let mut searcher = Searcher {
+        tt,
+        history: history.to_vec(),
+        nodes: 0,
+        stop,
+        deadline: time_limits.map(|(_, maximum)| start + maximum),
+        node_limit: limits.nodes,
+        aborted: false,
+        eval_noise: limits.eval_noise,
+        tablebases: tablebases.filter(|tablebases| !tablebases.is_empty()),
+        tbhits: 0,
+        tb_errors: Vec::new(),
+        tb_errors_reported: 0,
+        killers: vec![[None; 2]; MAX_PLY as usize + 1],
+        history_scores: Box::new([[[0; 64]; 64]; 2]),
+    };
+
+    // Obtener todos los movimientos posibles (incluidas las coronaciones menores)
+    let mut root_moves = board.generate_legal_moves();
+
+    // En un final de las tablas se descartan las jugadas que empeoran el resultado y, si se gana,
+    // las que tardan más en avanzar peón, capturar o dar mate: así se acaba ganando seguro
+    let ranked = match searcher.tablebases.map(|tablebases| tablebases.rank_root_moves(&mut board, history)) {
+        Some(Ok(ranked)) => ranked,
+        Some(Err(error)) => {
+            searcher.tablebase_error(error);
+            None
         }
+        None => None,
+    };
+    if let Some(ranked) = ranked {
+        searcher.tbhits += ranked.len() as u64;
+        let best_rank = ranked.iter().map(|&(_, rank)| rank).max();
+        root_moves = ranked.into_iter().filter(|&(_, rank)| Some(rank) == best_rank).map(|(mv, _)| mv).collect();
+    }
+
+    // Mezclar movimientos para añadir variedad si los puntajes son iguales: la ordenación
+    // es estable, así que el azar solo decide entre jugadas con la misma prioridad
+    if limits.random_ties {
+        let mut rng = rand::thread_rng();
+        root_moves.shuffle(&mut rng);
     }
+    let tt_move = searcher.tt.probe(board.hash, 0).and_then(|entry| entry.best_move);
+    searcher.order_moves(&board, &mut root_moves, tt_move, 0);
 
-    // Mezclar movimientos para añadir variedad si los puntajes son iguales y mejorar poda
-    let mut rng = rand::thread_rng();
-    all_moves.shuffle(&mut rng);
+    let mut best_move = root_moves.first().copied();
+    let mut last_score = 0;
+    let mut completed_depth = 0;
+    for depth in 1..=max_depth {
+        let Some((mv, score, pv)) = searcher.aspiration_search(&mut board, depth, &root_moves, last_score) else {
+            break;
+        };
+        best_move = Some(mv);
+        last_score = score;
+        completed_depth = depth;
+        searcher.tt.store(board.hash, depth, score, Bound::Exact, Some(mv), 0);
+
+        // La mejor jugada se prueba primero en la siguiente iteración, para podar más
+        if let Some(i) = root_moves.iter().position(|&m| m == mv) {
+            root_moves[..=i].rotate_right(1);
+        }
+
+        on_iteration(&SearchInfo {
+            depth,
+            score,
+            nodes: searcher.nodes,
+            elapsed: start.elapsed(),
+            pv,
+            hashfull: searcher.tt.hashfull(),
+            tbhits: searcher.tbhits,
+            tb_errors: searcher.tb_errors[searcher.tb_errors_reported..].to_vec(),
+        });
+        searcher.tb_errors_reported = searcher.tb_errors.len();
+
+        // Un mate encontrado no va a mejorar buscando más profundo
+        if score.abs() >= MATE_THRESHOLD {
+            break;
+        }
+        // Cada iteración cuesta varias veces la anterior: pasada la mitad del tiempo previsto
+        // la siguiente ya no terminaría a tiempo
+        if time_limits.is_some_and(|(optimum, _)| start.elapsed() >= optimum / 2) {
+            break;
+        }
+    }
 
-    for (from, to) in all_moves {
-        let mut new_board = board.clone();
-        new_board.move_piece(from, to);
-        
-        let value = minimax(&new_board, MAX_DEPTH - 1, alpha, beta, false, color);
-        
-        if value > best_value {
-            best_value = value;
-            best_move = Some((from, to));
+    // Nivel de dificultad: se cambia la mejor jugada por otra casi igual de buena. Se vuelven a
+    // puntuar todas a la última profundidad completa con el tiempo que queda; si se acaba antes
+    // (o llega la orden de parar) se juega la mejor.
+    if limits.move_margin > 0 && completed_depth > 0 && !searcher.stop.load(Ordering::Relaxed) {
+        searcher.aborted = false;
+        let mut rng = rand::thread_rng();
+        if let Some(mv) = searcher.pick_within_margin(&mut board, completed_depth, &root_moves, limits.move_margin, &mut rng) {
+            best_move = Some(mv);
         }
-        alpha = cmp::max(alpha, best_value);
     }
 
     best_move
 }
 
-fn minimax(board: &Board, depth: i32, mut alpha: i32, mut beta: i32, is_maximizing: bool, my_color: PieceColor) -> i32 {
-    if depth == 0 {
-        return evaluate(board, my_color);
-    }
-
-    // Verificar fin del juego
-    let current_turn_color = if is_maximizing { my_color } else { my_color.opposite() };
-    
-    let mut all_moves = Vec::new();
-    for y in 0..8 {
-        for x in 0..8 {
-            if let Some(piece) = board.grid[y][x] {
-                if piece.color == current_turn_color {
-                    let moves = board.get_valid_moves((x, y));
-                    for dest in moves {
-                        all_moves.push(((x, y), dest));
-                    }
-                }
+// Estado compartido por todos los nodos de una búsqueda
+struct Searcher<'a> {
+    tt: &'a mut TranspositionTable,
+    history: Vec<u64>, // Hashes desde el inicio de la partida hasta el nodo actual
+    nodes: u64,
+    stop: &'a AtomicBool,
+    deadline: Option<Instant>,
+    node_limit: Option<u64>,
+    aborted: bool,
+    eval_noise: i32, // Ruido máximo de la evaluación, según el nivel de dificultad
+    tablebases: Option<&'a Tablebases>,
+    tbhits: u64,
+    tb_errors: Vec<CorruptTable>, // Sin repetir; los primeros `tb_errors_reported` ya se han comunicado
+    tb_errors_reported: usize,
+    killers: Vec<[Option<Move>; 2]>, // Jugadas tranquilas que provocaron un corte, por ply
+    history_scores: Box<[[[i32; 64]; 64]; 2]>, // Éxito de cada jugada tranquila (color, origen, destino)
+}
+
+impl Searcher<'_> {
+    // El límite de nodos se comprueba siempre; la orden de parar y el reloj, cada 1024 nodos
+    fn should_stop(&mut self) -> bool {
+        if self.node_limit.is_some_and(|limit| self.nodes > limit) {
+            self.aborted = true;
+        }
+        if !self.aborted && self.nodes.is_multiple_of(1024) {
+            self.aborted = self.stop.load(Ordering::Relaxed)
+                || self.deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        }
+        self.aborted
+    }
+
+    // Resultado según las tablas de finales, si las hay y cubren la posición
+    fn probe_wdl(&mut self, board: &mut Board) -> Option<Wdl> {
+        match self.tablebases?.probe_wdl(board) {
+            Ok(wdl) => wdl,
+            Err(error) => {
+                self.tablebase_error(error);
+                None
+            }
+        }
+    }
+
+    // Un archivo dañado se sigue sondeando (y fallando) en cada posición: se avisa una vez por búsqueda
+    fn tablebase_error(&mut self, error: CorruptTable) {
+        if !self.tb_errors.contains(&error) {
+            self.tb_errors.push(error);
+        }
+    }
+
+    // Evaluación estática con el ruido del nivel de dificultad. El ruido sale del hash, así que
+    // cada posición se valora siempre igual y la IA se equivoca de forma coherente.
+    fn evaluate(&self, board: &Board) -> i32 {
+        let score = evaluate(board, board.side_to_move);
+        if self.eval_noise == 0 {
+            return score;
+        }
+        let mixed = board.hash.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32;
+        let noise = (mixed % (2 * self.eval_noise as u64 + 1)) as i32 - self.eval_noise;
+        score + noise
+    }
+
+    // Puntúa cada jugada a `depth` y elige una con `choose_within_margin`; None si la búsqueda se interrumpe
+    fn pick_within_margin(&mut self, board: &mut Board, depth: i32, moves: &[Move], margin: i32, rng: &mut impl Rng) -> Option<Move> {
+        let mut scored = Vec::with_capacity(moves.len());
+        let mut child_pv = Vec::new();
+        for &mv in moves {
+            let undo = board.make_move(mv);
+            self.history.push(board.hash);
+            let value = -self.negamax(board, depth - 1, 1, -INFINITY, INFINITY, &mut child_pv);
+            self.history.pop();
+            board.unmake_move(undo);
+            if self.aborted {
+                return None;
             }
+            scored.push((mv, value));
         }
+
+        choose_within_margin(&scored, margin, rng).map(|(mv, _)| mv)
     }
 
-    if all_moves.is_empty() {
-        // No hay movimientos. Jaque Mate o Ahogado.
-        if board.is_in_check(current_turn_color) {
-            return if is_maximizing { -99999 } else { 99999 }; // Jaque Mate
+    // Ventana de aspiración: se busca primero cerca de la puntuación de la iteración anterior
+    // y, si el resultado cae fuera, se repite con una ventana cada vez más ancha
+    fn aspiration_search(&mut self, board: &mut Board, depth: i32, moves: &[Move], last_score: i32) -> Option<(Move, i32, Vec<Move>)> {
+        let mut delta = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = if depth >= 4 && last_score.abs() < MATE_THRESHOLD {
+            (last_score - delta, last_score + delta)
         } else {
-            return 0; // Ahogado
+            (-INFINITY, INFINITY)
+        };
+
+        loop {
+            let (mv, score, pv) = self.search_root(board, depth, moves, alpha, beta)?;
+            if score <= alpha {
+                alpha = cmp::max(score - delta, -INFINITY);
+            } else if score >= beta {
+                beta = cmp::min(score + delta, INFINITY);
+            } else {
+                return Some((mv, score, pv));
+            }
+            delta *= 2;
         }
     }
 
-    if is_maximizing {
-        let mut max_eval = i32::MIN;
-        for (from, to) in all_moves {
-            let mut new_board = board.clone();
-            new_board.move_piece(from, to);
-            let eval = minimax(&new_board, depth - 1, alpha, beta, false, my_color);
-            max_eval = cmp::max(max_eval, eval);
-            alpha = cmp::max(alpha, eval);
-            if beta <= alpha {
+    // Mejor jugada, puntuación y variante principal a `depth`; None si la búsqueda se interrumpió.
+    // Si ninguna jugada supera `alpha`, la puntuación es solo una cota superior.
+    fn search_root(&mut self, board: &mut Board, depth: i32, moves: &[Move], mut alpha: i32, beta: i32) -> Option<(Move, i32, Vec<Move>)> {
+        let mut best = None;
+        let mut best_value = -INFINITY;
+        let mut child_pv = Vec::new();
+
+        for (i, &mv) in moves.iter().enumerate() {
+            let undo = board.make_move(mv);
+            self.history.push(board.hash);
+            // La primera jugada con la ventana completa; las demás solo se comprueban
+            let mut value = if i == 0 {
+                -self.negamax(board, depth - 1, 1, -beta, -alpha, &mut child_pv)
+            } else {
+                -self.negamax(board, depth - 1, 1, -alpha - 1, -alpha, &mut child_pv)
+            };
+            if i > 0 && value > alpha && value < beta {
+                value = -self.negamax(board, depth - 1, 1, -beta, -alpha, &mut child_pv);
+            }
+            self.history.pop();
+            board.unmake_move(undo);
+            if self.aborted {
+                return None;
+            }
+
+            if value > best_value {
+                best_value = value;
+                let mut pv = vec![mv];
+                pv.extend_from_slice(&child_pv);
+                best = Some((mv, value, pv));
+            }
+            alpha = cmp::max(alpha, value);
+            if alpha >= beta {
                 break;
             }
         }
-        max_eval
-    } else {
-        let mut min_eval = i32::MAX;
-        for (from, to) in all_moves {
-            let mut new_board = board.clone();
-            new_board.move_piece(from, to);
-            let eval = minimax(&new_board, depth - 1, alpha, beta, true, my_color);
-            min_eval = cmp::min(min_eval, eval);
-            beta = cmp::min(beta, eval);
-            if beta <= alpha {
+
+        best
+    }
+
+    // Negamax: la puntuación es siempre la del bando que mueve, así que la del rival se niega.
+    // Búsqueda de variante principal: tras la primera jugada, las demás se prueban con una ventana
+    // nula y solo se vuelven a buscar con la ventana completa si la mejoran.
+    fn negamax(&mut self, board: &mut Board, mut depth: i32, ply: i32, mut alpha: i32, beta: i32, pv: &mut Vec<Move>) -> i32 {
+        pv.clear();
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+
+        // Volver a una posición ya vista es tablas: la IA la evita si va ganando y la busca si va perdiendo
+        if board.repetition_count(&self.history) >= 2 || board.is_fifty_move_draw() {
+            return 0;
+        }
+        if ply >= MAX_PLY {
+            return self.evaluate(board);
+        }
+
+        let color = board.side_to_move;
+        let in_check = board.is_in_check(color);
+        // Extensión de jaque: no se llega al horizonte en mitad de un jaque
+        if in_check {
+            depth += 1;
+        }
+
+        // En el horizonte se siguen las capturas pendientes antes de evaluar
+        if depth <= 0 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+
+        let is_pv_node = beta - alpha > 1;
+        let alpha_orig = alpha;
+
+        // Posición ya buscada a suficiente profundidad, quizá por otro orden de jugadas.
+        // En la variante principal no se corta, para no perderla.
+        let tt_entry = self.tt.probe(board.hash, ply);
+        if let Some(entry) = tt_entry
+            && entry.depth >= depth
+            && !is_pv_node {
+            let usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.score >= beta,
+                Bound::Upper => entry.score <= alpha,
+            };
+            if usable {
+                return entry.score;
+            }
+        }
+
+        // Tablas de finales: justo tras una captura o un avance de peón (antes no cambia el
+        // material) y con pocas piezas se sabe el resultado. Una victoria o una derrota no dicen
+        // cómo llegar al mate, así que solo se corta si bastan para salir de la ventana.
+        if board.halfmove_clock == 0
+            && let Some(wdl) = self.probe_wdl(board) {
+            self.tbhits += 1;
+            // Las victorias y derrotas que anula la regla de los 50 movimientos casi son tablas
+            let (score, bound) = match wdl {
+                Wdl::Win => (TB_WIN - ply, Bound::Lower),
+                Wdl::Loss => (-TB_WIN + ply, Bound::Upper),
+                _ => (wdl as i32, Bound::Exact),
+            };
+            let cutoff = match bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if cutoff {
+                self.tt.store(board.hash, cmp::min(depth + 6, MAX_DEPTH), score, bound, None, ply);
+                return score;
+            }
+        }
+
+        // Poda de jugada nula: si aun pasando el turno el rival no baja de beta, la posición es
+        // tan buena que no hace falta buscarla entera. Sin piezas (solo Rey y peones) el zugzwang
+        // es frecuente y pasar sería una ventaja falsa, así que ahí no se usa.
+        let mut child_pv = Vec::new();
+        if !is_pv_node
+            && !in_check
+            && depth >= 3
+            && beta.abs() < MATE_THRESHOLD
+            && board.has_non_pawn_material(color)
+            && self.evaluate(board) >= beta {
+            let reduction = if depth >= 7 { 3 } else { 2 };
+            let undo = board.make_null_move();
+            self.history.push(board.hash);
+            let value = -self.negamax(board, depth - 1 - reduction, ply + 1, -beta, -beta + 1, &mut child_pv);
+            self.history.pop();
+            board.unmake_null_move(undo);
+            if self.aborted {
+                return 0;
+            }
+            if value >= beta {
+                return value;
+            }
+        }
+
+        let mut all_moves = board.generate_legal_moves();
+
+        if all_moves.is_empty() {
+            // No hay movimientos. Jaque Mate o Ahogado.
+            // Cuanto antes llegue el mate, más extrema la puntuación
+            return if in_check { -(MATE_SCORE - ply) } else { 0 };
+        }
+
+        self.order_moves(board, &mut all_moves, tt_entry.and_then(|entry| entry.best_move), ply);
+        let killers = self.killers.get(ply as usize).copied().unwrap_or([None; 2]);
+
+        let mut best_value = -INFINITY;
+        let mut best_move = None;
+        for (i, mv) in all_moves.into_iter().enumerate() {
+            let is_quiet = !mv.is_capture && mv.promotion.is_none();
+            let undo = board.make_move(mv);
+            self.history.push(board.hash);
+            let gives_check = board.is_in_check(board.side_to_move);
+
+            let value = if i == 0 {
+                -self.negamax(board, depth - 1, ply + 1, -beta, -alpha, &mut child_pv)
+            } else {
+                // Reducción de jugadas tardías: las jugadas tranquilas que llegan tarde en la
+                // ordenación rara vez son buenas y se buscan antes con menos profundidad
+                let reduction = if depth >= 3
+                    && i >= LMR_MIN_MOVES
+                    && is_quiet
+                    && !in_check
+                    && !gives_check
+                    && !killers.contains(&Some(mv)) {
+                    if i >= 2 * LMR_MIN_MOVES && depth >= 6 { 2 } else { 1 }
+                } else {
+                    0
+                };
+
+                let mut value = -self.negamax(board, depth - 1 - reduction, ply + 1, -alpha - 1, -alpha, &mut child_pv);
+                if value > alpha && reduction > 0 {
+                    value = -self.negamax(board, depth - 1, ply + 1, -alpha - 1, -alpha, &mut child_pv);
+                }
+                if value > alpha && value < beta {
+                    value = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+                }
+                value
+            };
+            self.history.pop();
+            board.unmake_move(undo);
+            if self.aborted {
+                return 0;
+            }
+
+            if value > best_value {
+                best_value = value;
+                best_move = Some(mv);
+                pv.clear();
+                pv.push(mv);
+                pv.extend_from_slice(&child_pv);
+            }
+            alpha = cmp::max(alpha, value);
+            if alpha >= beta {
+                self.record_cutoff(color, mv, depth, ply);
                 break;
             }
         }
-        min_eval
+
+        let bound = if best_value <= alpha_orig {
+            Bound::Upper
+        } else if best_value >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.tt.store(board.hash, depth, best_value, bound, best_move, ply);
+        best_value
     }
-}
 
-fn evaluate(board: &Board, my_color: PieceColor) -> i32 {
-    let mut score = 0;
+    // Búsqueda de quietud: solo capturas y coronaciones, hasta llegar a una posición tranquila.
+    // El bando que mueve puede quedarse con la evaluación estática (stand pat) si no le conviene
+    // capturar; en jaque no hay esa opción y se prueban todas las respuestas.
+    fn quiescence(&mut self, board: &mut Board, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
 
-    for y in 0..8 {
-        for x in 0..8 {
-            if let Some(piece) = board.grid[y][x] {
-                let value = get_piece_value(piece.piece_type);               
-                if piece.color == my_color {
-                    score += value;
-                    score += get_position_bonus(piece, x, y);
-                } else {
-                    score -= value;
-                    score -= get_position_bonus(piece, x, y);
+        // En jaque hay que mirar todas las respuestas; si no, solo capturas y coronaciones
+        let in_check = board.is_in_check(board.side_to_move);
+        let mut moves = if in_check { board.generate_legal_moves() } else { board.generate_legal_captures() };
+        if moves.is_empty() {
+            if in_check {
+                return -(MATE_SCORE - ply);
+            }
+            if board.is_stalemate(board.side_to_move) {
+                return 0;
+            }
+        }
+
+        let stand_pat = self.evaluate(board);
+        if ply >= MAX_PLY {
+            return stand_pat;
+        }
+        if !in_check {
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = cmp::max(alpha, stand_pat);
+        }
+
+        self.order_moves(board, &mut moves, None, ply);
+
+        let mut best = if in_check { -INFINITY } else { stand_pat };
+        for mv in moves {
+            // Poda delta: ni con el material ganado se acercaría a alfa
+            if !in_check && stand_pat + material_gain(board, mv) + DELTA_MARGIN < alpha {
+                continue;
+            }
+
+            let undo = board.make_move(mv);
+            let value = -self.quiescence(board, ply + 1, -beta, -alpha);
+            board.unmake_move(undo);
+
+            best = cmp::max(best, value);
+            alpha = cmp::max(alpha, value);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+
+    // Ordena las jugadas de más a menos prometedoras: la mejor jugada de la tabla de
+    // transposiciones, las capturas y coronaciones (MVV-LVA: víctima más valiosa con el atacante
+    // más barato), las jugadas asesinas de este ply y el resto según la tabla de historia
+    fn order_moves(&self, board: &Board, moves: &mut [Move], tt_move: Option<Move>, ply: i32) {
+        let killers = self.killers.get(ply as usize).copied().unwrap_or([None; 2]);
+        let color = board.side_to_move as usize;
+        moves.sort_by_cached_key(|&mv| {
+            let priority = if Some(mv) == tt_move {
+                1_000_000
+            } else if mv.is_capture || mv.promotion.is_some() {
+                let attacker = board.piece_at(mv.from).map_or(0, |piece| get_piece_value(piece.piece_type));
+                100_000 + material_gain(board, mv) * 10 - attacker
+            } else if Some(mv) == killers[0] {
+                90_000
+            } else if Some(mv) == killers[1] {
+                80_000
+            } else {
+                self.history_scores[color][square(mv.from)][square(mv.to)]
+            };
+            cmp::Reverse(priority)
+        });
+    }
+
+    // Una jugada tranquila que provoca un corte se recuerda como asesina de su ply
+    // y suma en la tabla de historia, más cuanto más profunda era la búsqueda
+    fn record_cutoff(&mut self, color: PieceColor, mv: Move, depth: i32, ply: i32) {
+        if mv.is_capture || mv.promotion.is_some() {
+            return;
+        }
+        if let Some(killers) = self.killers.get_mut(ply as usize)
+            && killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+
+        let score = &mut self.history_scores[color as usize][square(mv.from)][square(mv.to)];
+        *score += depth * depth;
+        // Al acercarse a las jugadas asesinas se reduce toda la tabla, para que no las adelante
+        if *score >= 50_000 {
+            for row in self.history_scores[color as usize].iter_mut() {
+                for score in row.iter_mut() {
+                    *score /= 2;
                 }
             }
         }
     }
-    score
 }
 
-// Bonificaciones posicionales simples
-fn get_position_bonus(piece: Piece, x: usize, y: usize) -> i32 {
-    // Invertir y para las negras para simplificar
-    let center_bonus = if (3..=4).contains(&x) && (3..=4).contains(&y) {
-        20
-    } else if (2..=5).contains(&x) && (2..=5).contains(&y) {
-        10
+// Material que gana una jugada: la pieza capturada y, al coronar, la diferencia con el peón
+fn material_gain(board: &Board, mv: Move) -> i32 {
+    let captured = if mv.is_en_passant {
+        get_piece_value(PieceType::Pawn)
     } else {
-        0
+        board.piece_at(mv.to).map_or(0, |piece| get_piece_value(piece.piece_type))
     };
+    let promotion = mv.promotion.map_or(0, |piece_type| get_piece_value(piece_type) - get_piece_value(PieceType::Pawn));
+    captured + promotion
+}
+
+// Elige al azar entre las jugadas puntuadas que pierden como mucho `margin` respecto a la mejor,
+// con más probabilidad cuanto mejores son
+fn choose_within_margin(scored: &[(Move, i32)], margin: i32, rng: &mut impl Rng) -> Option<(Move, i32)> {
+    let best = scored.iter().map(|&(_, value)| value).max()?;
+    let candidates: Vec<(Move, i32)> = scored.iter().copied().filter(|&(_, value)| value >= best - margin).collect();
+    candidates.choose_weighted(rng, |&(_, value)| margin + 1 - (best - value)).ok().copied()
+}
 
-    let mut bonus = center_bonus;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::sync::Arc;
+    use std::thread;
+    use crate::board::{parse_square, START_FEN};
 
-    match piece.piece_type {
-        PieceType::Pawn => {
-            // Avanzar peones es generalmente bueno
-             let rank = if piece.color == PieceColor::White { 7 - y } else { y };
-             bonus += rank as i32 * 10;
+    // Juega las jugadas en notación de coordenadas y devuelve el historial de hashes
+    fn play(board: &mut Board, moves: &str) -> Vec<u64> {
+        let mut history = vec![board.hash];
+        for text in moves.split_whitespace() {
+            let mv = Move::new(parse_square(&text[..2]).unwrap(), parse_square(&text[2..]).unwrap());
+            board.move_piece(mv);
+            history.push(board.hash);
         }
-        PieceType::Knight => {
-             // Los caballos odian los bordes
-             if x == 0 || x == 7 || y == 0 || y == 7 {
-                 bonus -= 30;
-             }
+        history
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn time_limits() {
+        let clock = |wtime, btime, winc, binc, movestogo| SearchLimits {
+            wtime: Some(ms(wtime)),
+            btime: Some(ms(btime)),
+            winc: Some(ms(winc)),
+            binc: Some(ms(binc)),
+            movestogo,
+            ..SearchLimits::default()
+        };
+
+        // Tiempo fijo, o ninguno
+        let movetime = SearchLimits { movetime: Some(ms(250)), ..SearchLimits::default() };
+        assert_eq!(movetime.time_limits(PieceColor::Black), Some((ms(250), ms(250))));
+        assert_eq!(SearchLimits::default().time_limits(PieceColor::White), None);
+        let infinite = SearchLimits { infinite: true, ..clock(60_000, 60_000, 0, 0, None) };
+        assert_eq!(infinite.time_limits(PieceColor::White), None);
+        let only_white = SearchLimits { wtime: Some(ms(60_000)), ..SearchLimits::default() };
+        assert_eq!(only_white.time_limits(PieceColor::Black), None);
+
+        // Un trigésimo del reloj más la mitad del incremento, hasta el triple más el incremento
+        let limits = clock(60_000, 30_000, 1_000, 0, None);
+        assert_eq!(limits.time_limits(PieceColor::White), Some((ms(2_500), ms(7_000))));
+        assert_eq!(limits.time_limits(PieceColor::Black), Some((ms(1_000), ms(3_000))));
+        // Con movestogo el reloj se reparte entre esas jugadas
+        let limits = clock(60_000, 60_000, 0, 0, Some(10));
+        assert_eq!(limits.time_limits(PieceColor::White), Some((ms(6_000), ms(18_000))));
+
+        // Con poco reloj nunca se pasa de lo que queda menos el margen, aunque haya incremento
+        let limits = clock(1_000, 200, 0, 1_000, Some(1));
+        assert_eq!(limits.time_limits(PieceColor::White), Some((ms(950), ms(950))));
+        assert_eq!(limits.time_limits(PieceColor::Black), Some((ms(150), ms(150))));
+        let limits = clock(40, 40, 2_000, 2_000, None);
+        assert_eq!(limits.time_limits(PieceColor::White), Some((Duration::ZERO, Duration::ZERO)));
+    }
+
+    #[test]
+    fn stop_ends_the_search() {
+        // Sin la orden de parar, esta búsqueda duraría una hora
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let board = Board::new();
+                let limits = SearchLimits { movetime: Some(Duration::from_secs(3600)), ..SearchLimits::default() };
+                search(&board, &[board.hash], &limits, None, &mut TranspositionTable::new(1), &stop, |_| {})
+            })
+        };
+        thread::sleep(Duration::from_millis(200));
+        let stopped = Instant::now();
+        stop.store(true, Ordering::Relaxed);
+        let best = worker.join().unwrap().unwrap();
+        assert!(stopped.elapsed() < Duration::from_secs(1));
+        assert!(Board::new().generate_legal_moves().contains(&best));
+    }
+
+    #[test]
+    fn stop_ends_the_ai_move_for_either_colour() {
+        // La IA puede llevar cualquiera de los dos bandos; en los dos casos volver al menú la para
+        for fen in [START_FEN, "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"] {
+            let board = Board::from_fen(fen).unwrap();
+            let stop = Arc::new(AtomicBool::new(false));
+            let worker = {
+                let (board, stop) = (board.clone(), Arc::clone(&stop));
+                thread::spawn(move || {
+                    get_best_move(&board, &[board.hash], Difficulty::Maximum, None, None, &mut TranspositionTable::new(1), &stop).0
+                })
+            };
+            thread::sleep(Duration::from_millis(100));
+            let stopped = Instant::now();
+            stop.store(true, Ordering::Relaxed);
+            let best = worker.join().unwrap().unwrap();
+            assert!(stopped.elapsed() < Duration::from_secs(1), "{}", fen);
+            assert!(board.clone().generate_legal_moves().contains(&best), "{}", fen);
+
+            // Parada antes de empezar, también con el margen de los niveles bajos: alguna jugada legal
+            for difficulty in Difficulty::ALL {
+                let stop = AtomicBool::new(true);
+                let best = get_best_move(&board, &[board.hash], difficulty, None, None, &mut TranspositionTable::new(1), &stop).0.unwrap();
+                assert!(board.clone().generate_legal_moves().contains(&best), "{} {}", fen, difficulty);
+            }
         }
-        _ => {}
     }
 
-    bonus
+    #[test]
+    fn repetition_is_a_draw() {
+        // Con una dama de menos, volver a una posición ya vista (tablas) es lo mejor para las negras
+        let mut board = Board::from_fen("6k1/8/8/8/8/8/8/1Q2K3 w - - 10 40").unwrap();
+        let history = play(&mut board, "b1c1 g8h8 c1b1");
+        let limits = SearchLimits { depth: Some(3), ..SearchLimits::default() };
+        let mut score = None;
+        let best = search(&board, &history, &limits, None, &mut TranspositionTable::new(1), &AtomicBool::new(false), |info| score = Some(info.score)).unwrap();
+        assert_eq!(best.to_string(), "h8g8");
+        assert_eq!(score, Some(0));
+
+        // Sin esa posición en el historial, sigue siendo una dama de menos
+        let mut score = None;
+        search(&board, &[board.hash], &limits, None, &mut TranspositionTable::new(1), &AtomicBool::new(false), |info| score = Some(info.score));
+        assert!(score.unwrap() < 0);
+    }
+
+    #[test]
+    fn difficulty_limits() {
+        let limits = Difficulty::ALL.map(Difficulty::limits);
+        for (difficulty, limits) in Difficulty::ALL.iter().zip(&limits) {
+            // Todos los niveles juegan con tiempo fijo y varían las partidas
+            assert!(limits.movetime.is_some() && limits.random_ties, "{}", difficulty);
+            assert!(!limits.infinite && limits.nodes.is_none(), "{}", difficulty);
+        }
+        // Cada nivel busca más y más tiempo, con menos ruido y menos margen, que el anterior
+        for pair in limits.windows(2) {
+            let (easier, harder) = (&pair[0], &pair[1]);
+            assert!(easier.depth.unwrap_or(MAX_DEPTH) < harder.depth.unwrap_or(MAX_DEPTH));
+            assert!(easier.movetime < harder.movetime);
+            assert!(easier.eval_noise >= harder.eval_noise && easier.move_margin >= harder.move_margin);
+        }
+        // Los dos más altos juegan la mejor jugada que encuentran; el máximo, sin límite de profundidad
+        for difficulty in [Difficulty::Hard, Difficulty::Maximum] {
+            assert_eq!((difficulty.limits().eval_noise, difficulty.limits().move_margin), (0, 0));
+        }
+        assert_eq!(Difficulty::Maximum.limits().depth, None);
+    }
+
+    #[test]
+    fn choice_within_margin() {
+        let moves: Vec<Move> = Board::new().generate_legal_moves().into_iter().take(5).collect();
+        let scored: Vec<(Move, i32)> = moves.iter().copied().zip([10, 40, -200, 25, 39]).collect();
+        let mut chosen = Vec::new();
+        for seed in 0..200 {
+            let (mv, value) = choose_within_margin(&scored, 30, &mut StdRng::seed_from_u64(seed)).unwrap();
+            assert!(value >= 40 - 30, "{:?} {}", mv, value);
+            // Con la misma semilla se elige siempre la misma
+            assert_eq!(choose_within_margin(&scored, 30, &mut StdRng::seed_from_u64(seed)), Some((mv, value)));
+            chosen.push(mv);
+        }
+        // Todas las que están dentro del margen salen alguna vez, y ninguna más
+        for (mv, value) in &scored {
+            assert_eq!(chosen.contains(mv), *value >= 10, "{:?} {}", mv, value);
+        }
+        // Sin margen solo vale la mejor
+        assert_eq!(choose_within_margin(&scored, 0, &mut StdRng::seed_from_u64(1)), Some(scored[1]));
+        assert_eq!(choose_within_margin(&[], 30, &mut StdRng::seed_from_u64(1)), None);
+    }
+
+    #[test]
+    fn margin_pass_keeps_the_clock() {
+        // Volver a puntuar todas las jugadas no puede pasarse del tiempo de la jugada
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let limits = SearchLimits {
+            movetime: Some(Duration::from_millis(300)),
+            move_margin: 50,
+            ..SearchLimits::default()
+        };
+        let start = Instant::now();
+        let best = search(&board, &[board.hash], &limits, None, &mut TranspositionTable::new(1), &AtomicBool::new(false), |_| {}).unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500), "{:?}", start.elapsed());
+        assert!(board.clone().generate_legal_moves().contains(&best));
+    }
 }
@@ -0,0 +1,103 @@
+use std::sync::atomic::AtomicBool;
+use std::time::Instant;
+use crate::ai::{self, SearchLimits};
+use crate::board::Board;
+use crate::transposition::TranspositionTable;
+
+// Profundidad por defecto de cada búsqueda
+const DEFAULT_DEPTH: i32 = 7;
+
+// Aperturas, medios juegos y finales variados: las posiciones de referencia de perft y algunas más
+const POSITIONS: [&str; 10] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "6k1/5p1p/6p1/8/8/6P1/5P1P/3R2K1 w - - 0 1",
+    "8/k7/3p4/p2P1p2/P2P1P2/8/8/K7 w - - 0 1",
+    "8/8/8/8/8/2k5/8/2K1Q3 w - - 0 1",
+];
+
+// Problemas tácticos con una única jugada ganadora (en coordenadas), para medir también la fuerza:
+// mates en una, dos y cuatro, una horquilla, una desviación, un ataque en rayos X
+// y una coronación en caballo
+const SUITE: [(&str, &str); 8] = [
+    ("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4", "h5f7"),
+    ("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", "d1d8"),
+    ("6rk/6pp/8/6N1/8/8/8/6K1 w - - 0 1", "g5f7"),
+    ("r3k3/8/8/3N4/8/8/8/4K3 w - - 0 1", "d5c7"),
+    ("r1b2k1r/ppp1bppp/8/1B1Q4/5q2/2P5/PPP2PPP/R3R1K1 w - - 1 1", "d5d8"),
+    ("1r5k/6pp/8/3Q2N1/8/8/8/6K1 w - - 0 1", "g5f7"),
+    ("q7/8/2k5/8/8/8/8/1B4K1 w - - 0 1", "b1e4"),
+    ("8/4P1k1/3q4/8/8/8/8/K7 w - - 0 1", "e7e8n"),
+];
+
+// Subcomando `chess-uci bench [profundidad]`: busca cada posición a profundidad fija y muestra
+// los nodos y la velocidad. El total de nodos es una firma de la búsqueda: cambia si cambia
+// su comportamiento, y menos nodos para la misma profundidad significa una poda más eficaz.
+pub fn run_command(args: &[String]) {
+    let depth = match args.first().map(|d| d.parse::<i32>()) {
+        None => DEFAULT_DEPTH,
+        Some(Ok(depth)) if (1..=ai::MAX_DEPTH).contains(&depth) => depth,
+        _ => {
+            eprintln!("Uso: chess-uci bench [profundidad]");
+            std::process::exit(1);
+        }
+    };
+    let limits = SearchLimits { depth: Some(depth), ..SearchLimits::default() };
+
+    let start = Instant::now();
+    let mut total_nodes = 0;
+    for (i, fen) in POSITIONS.iter().enumerate() {
+        let (best, score, nodes) = search_position(fen, &limits);
+        total_nodes += nodes;
+        println!("{:>2}. {:<6} {:>6} {:>10} nodos   {}", i + 1, best, score, nodes, fen);
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    println!();
+    println!("Nodos: {}", total_nodes);
+    println!("Tiempo: {:.3} s ({:.0} nodos/s)", elapsed, total_nodes as f64 / elapsed.max(1e-9));
+
+    // Fuera del tiempo medido: cuántos problemas resuelve a la misma profundidad
+    println!();
+    let mut solved = 0;
+    for (i, (fen, expected)) in SUITE.iter().enumerate() {
+        let (best, _, _) = search_position(fen, &limits);
+        let ok = best == *expected;
+        solved += ok as usize;
+        println!("{:>2}. {:<6} {:<6} {}   {}", i + 1, best, expected, if ok { "bien" } else { "mal " }, fen);
+    }
+    println!();
+    println!("Problemas resueltos: {}/{}", solved, SUITE.len());
+}
+
+// Busca una posición con una tabla nueva, para que el resultado no dependa del orden,
+// y devuelve la mejor jugada, su puntuación y los nodos
+fn search_position(fen: &str, limits: &SearchLimits) -> (String, i32, u64) {
+    let board = Board::from_fen(fen).expect("Las posiciones de bench son FEN válidas");
+    let mut tt = TranspositionTable::default();
+    let mut nodes = 0;
+    let mut score = 0;
+    let best = ai::search(&board, &[board.hash], limits, None, &mut tt, &AtomicBool::new(false), |info| {
+        nodes = info.nodes;
+        score = info.score;
+    });
+    (best.map_or_else(|| "-".to_string(), |mv| mv.to_string()), score, nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suite_solutions_are_legal() {
+        for (fen, expected) in SUITE {
+            let mut board = Board::from_fen(fen).unwrap();
+            assert!(board.generate_legal_moves().iter().any(|mv| mv.to_string() == expected), "{}", fen);
+        }
+    }
+}
@@ -0,0 +1,15 @@
+// Motor UCI y herramientas de consola, sin ventana. Se compila también sin la característica `gui`:
+//   cargo run --no-default-features --bin chess-uci
+//   cargo run --no-default-features --bin chess-uci -- perft <profundidad> ["<FEN>"]
+//   cargo run --no-default-features --bin chess-uci -- bench [profundidad]
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        // Cuenta los nodos del árbol de jugadas legales, desglosados por primera jugada
+        Some("perft") => chess::perft::run_command(&args[2..]),
+        // Mide la búsqueda de la IA (nodos y velocidad) en un conjunto fijo de posiciones
+        Some("bench") => chess::bench::run_command(&args[2..]),
+        // Sin subcomando (o con `uci`) habla el protocolo UCI por la entrada y salida estándar
+        _ => chess::uci::run(),
+    }
+}
@@ -0,0 +1,196 @@
+use crate::pieces::PieceColor;
+
+// Un bit por casilla. La casilla (x, y) ocupa el bit y * 8 + x, así que el bit 0 es a8 y el 63 es h1.
+pub type Bitboard = u64;
+
+pub const fn square(pos: (usize, usize)) -> usize {
+    pos.1 * 8 + pos.0
+}
+
+pub const fn position(square: usize) -> (usize, usize) {
+    (square % 8, square / 8)
+}
+
+pub const fn bit(square: usize) -> Bitboard {
+    1 << square
+}
+
+// Recorre las casillas activas de un bitboard, de menor a mayor
+pub struct Squares(Bitboard);
+
+impl Iterator for Squares {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            return None;
+        }
+        let square = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+}
+
+pub fn squares(bb: Bitboard) -> Squares {
+    Squares(bb)
+}
+
+// Casillas claras (a8 es clara)
+pub const LIGHT_SQUARES: Bitboard = 0xAA55_AA55_AA55_AA55;
+
+// Todas las casillas de una columna (0 = a)
+pub const fn file_mask(file: usize) -> Bitboard {
+    0x0101_0101_0101_0101 << file
+}
+
+// Las columnas vecinas, sin la propia
+pub const fn adjacent_files(file: usize) -> Bitboard {
+    let left = if file > 0 { file_mask(file - 1) } else { 0 };
+    let right = if file < 7 { file_mask(file + 1) } else { 0 };
+    left | right
+}
+
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [
+    (1, 2), (1, -2), (-1, 2), (-1, -2),
+    (2, 1), (2, -1), (-2, 1), (-2, -1),
+];
+
+const KING_OFFSETS: [(i32, i32); 8] = [
+    (0, 1), (0, -1), (1, 0), (-1, 0),
+    (1, 1), (1, -1), (-1, 1), (-1, -1),
+];
+
+// Las cuatro primeras direcciones aumentan el índice de casilla y las cuatro últimas lo disminuyen
+const DIRECTIONS: [(i32, i32); 8] = [
+    (1, 0), (0, 1), (1, 1), (-1, 1),
+    (-1, 0), (0, -1), (-1, -1), (1, -1),
+];
+const ROOK_DIRECTIONS: [usize; 4] = [0, 1, 4, 5];
+const BISHOP_DIRECTIONS: [usize; 4] = [2, 3, 6, 7];
+
+const fn leaper_attacks(offsets: &[(i32, i32); 8]) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut sq = 0;
+    while sq < 64 {
+        let (x, y) = ((sq % 8) as i32, (sq / 8) as i32);
+        let mut i = 0;
+        while i < offsets.len() {
+            let (nx, ny) = (x + offsets[i].0, y + offsets[i].1);
+            if nx >= 0 && nx < 8 && ny >= 0 && ny < 8 {
+                table[sq] |= 1 << (ny * 8 + nx);
+            }
+            i += 1;
+        }
+        sq += 1;
+    }
+    table
+}
+
+const fn pawn_attack_table() -> [[Bitboard; 64]; 2] {
+    let mut table = [[0; 64]; 2];
+    let mut sq = 0;
+    while sq < 64 {
+        let (x, y) = ((sq % 8) as i32, (sq / 8) as i32);
+        // Las Blancas avanzan hacia y = 0 y las Negras hacia y = 7
+        let mut color = 0;
+        while color < 2 {
+            let ny = if color == 0 { y - 1 } else { y + 1 };
+            if ny >= 0 && ny < 8 {
+                if x > 0 {
+                    table[color][sq] |= 1 << (ny * 8 + x - 1);
+                }
+                if x < 7 {
+                    table[color][sq] |= 1 << (ny * 8 + x + 1);
+                }
+            }
+            color += 1;
+        }
+        sq += 1;
+    }
+    table
+}
+
+// Rayos completos desde cada casilla en cada dirección, sin contar bloqueos
+const fn ray_table() -> [[Bitboard; 64]; 8] {
+    let mut table = [[0; 64]; 8];
+    let mut dir = 0;
+    while dir < 8 {
+        let (dx, dy) = DIRECTIONS[dir];
+        let mut sq = 0;
+        while sq < 64 {
+            let (mut x, mut y) = ((sq % 8) as i32 + dx, (sq / 8) as i32 + dy);
+            while x >= 0 && x < 8 && y >= 0 && y < 8 {
+                table[dir][sq] |= 1 << (y * 8 + x);
+                x += dx;
+                y += dy;
+            }
+            sq += 1;
+        }
+        dir += 1;
+    }
+    table
+}
+
+// Casillas por delante de cada peón en su columna y en las vecinas: si ningún peón rival
+// las ocupa, el peón es pasado
+const fn passed_pawn_table() -> [[Bitboard; 64]; 2] {
+    let mut table = [[0; 64]; 2];
+    let mut sq = 0;
+    while sq < 64 {
+        let (x, y) = (sq % 8, sq / 8);
+        let files = file_mask(x) | adjacent_files(x);
+        // Las Blancas avanzan hacia y = 0: por delante están las filas de índice menor
+        let white_ahead: Bitboard = (1 << (y * 8)) - 1;
+        let black_ahead: Bitboard = if y == 7 { 0 } else { !((1 << ((y + 1) * 8)) - 1) };
+        table[0][sq] = files & white_ahead;
+        table[1][sq] = files & black_ahead;
+        sq += 1;
+    }
+    table
+}
+
+static KNIGHT_ATTACKS: [Bitboard; 64] = leaper_attacks(&KNIGHT_OFFSETS);
+static KING_ATTACKS: [Bitboard; 64] = leaper_attacks(&KING_OFFSETS);
+static PAWN_ATTACKS: [[Bitboard; 64]; 2] = pawn_attack_table();
+static RAYS: [[Bitboard; 64]; 8] = ray_table();
+static PASSED_PAWN_MASKS: [[Bitboard; 64]; 2] = passed_pawn_table();
+
+pub fn knight_attacks(square: usize) -> Bitboard {
+    KNIGHT_ATTACKS[square]
+}
+
+pub fn king_attacks(square: usize) -> Bitboard {
+    KING_ATTACKS[square]
+}
+
+// Casillas que ataca un peón del color dado situado en `square`
+pub fn pawn_attacks(color: PieceColor, square: usize) -> Bitboard {
+    PAWN_ATTACKS[color as usize][square]
+}
+
+pub fn passed_pawn_mask(color: PieceColor, square: usize) -> Bitboard {
+    PASSED_PAWN_MASKS[color as usize][square]
+}
+
+// Ataques deslizantes clásicos: el rayo se corta en la primera pieza que encuentra (incluida)
+fn ray_attacks(dir: usize, square: usize, occupied: Bitboard) -> Bitboard {
+    let ray = RAYS[dir][square];
+    let blockers = ray & occupied;
+    if blockers == 0 {
+        return ray;
+    }
+    let first = if dir < 4 {
+        blockers.trailing_zeros() as usize
+    } else {
+        63 - blockers.leading_zeros() as usize
+    };
+    ray ^ RAYS[dir][first]
+}
+
+pub fn rook_attacks(square: usize, occupied: Bitboard) -> Bitboard {
+    ROOK_DIRECTIONS.iter().fold(0, |acc, &dir| acc | ray_attacks(dir, square, occupied))
+}
+
+pub fn bishop_attacks(square: usize, occupied: Bitboard) -> Bitboard {
+    BISHOP_DIRECTIONS.iter().fold(0, |acc, &dir| acc | ray_attacks(dir, square, occupied))
+}
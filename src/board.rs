@@ -1,311 +1,698 @@
+use std::fmt;
 use crate::pieces::{Piece, PieceColor, PieceType};
+use crate::moves::{Move, PROMOTION_PIECES};
+use crate::result::{EndReason, GameResult};
+use crate::zobrist;
+use crate::bitboard::{self, Bitboard, bit, position, square, squares};
 
 
 pub const BOARD_SIZE: usize = 8;
 
+// Posición inicial en notación FEN
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// Derechos de enroque que aún conserva cada bando
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CastlingRights {
+    pub white_kingside: bool,
+    pub white_queenside: bool,
+    pub black_kingside: bool,
+    pub black_queenside: bool,
+}
+
+impl CastlingRights {
+    pub fn none() -> Self {
+        Self { white_kingside: false, white_queenside: false, black_kingside: false, black_queenside: false }
+    }
+
+    pub fn get(&self, color: PieceColor, kingside: bool) -> bool {
+        match (color, kingside) {
+            (PieceColor::White, true) => self.white_kingside,
+            (PieceColor::White, false) => self.white_queenside,
+            (PieceColor::Black, true) => self.black_kingside,
+            (PieceColor::Black, false) => self.black_queenside,
+        }
+    }
+
+    pub fn set(&mut self, color: PieceColor, kingside: bool, allowed: bool) {
+        match (color, kingside) {
+            (PieceColor::White, true) => self.white_kingside = allowed,
+            (PieceColor::White, false) => self.white_queenside = allowed,
+            (PieceColor::Black, true) => self.black_kingside = allowed,
+            (PieceColor::Black, false) => self.black_queenside = allowed,
+        }
+    }
+
+    // Índice de 4 bits para las claves Zobrist
+    fn bits(&self) -> usize {
+        self.white_kingside as usize
+            | (self.white_queenside as usize) << 1
+            | (self.black_kingside as usize) << 2
+            | (self.black_queenside as usize) << 3
+    }
+
+    // Una Torre que sale de su esquina (o es capturada allí) pierde su enroque
+    fn remove_for_square(&mut self, pos: (usize, usize)) {
+        match pos {
+            (7, 7) => self.white_kingside = false,
+            (0, 7) => self.white_queenside = false,
+            (7, 0) => self.black_kingside = false,
+            (0, 0) => self.black_queenside = false,
+            _ => {}
+        }
+    }
+}
+
+// Posición completa: piezas, turno, enroques, captura al paso y contadores
 #[derive(Clone, Debug)]
 pub struct Board {
-    pub grid: [[Option<Piece>; BOARD_SIZE]; BOARD_SIZE],
-    pub last_move: Option<((usize, usize), (usize, usize))>,
+    squares: [Option<Piece>; 64],  // Pieza de cada casilla (índice y * 8 + x), para consultas rápidas
+    pieces: [[Bitboard; 6]; 2],    // Un bitboard por color y tipo de pieza
+    occupancy: [Bitboard; 2],      // Casillas ocupadas por cada color
+    pub side_to_move: PieceColor,
+    pub castling: CastlingRights,
+    pub en_passant: Option<(usize, usize)>, // Casilla saltada por el último avance doble de peón
+    pub halfmove_clock: u32,  // Medios movimientos desde la última captura o avance de peón
+    pub fullmove_number: u32, // Empieza en 1 y aumenta tras cada movimiento de las negras
+    pub hash: u64,            // Hash Zobrist de la posición, actualizado en cada jugada
 }
 
-impl Board {
-    pub fn new() -> Self {
-        let mut grid = [[None; BOARD_SIZE]; BOARD_SIZE];
-        
-        // Configurar Peones
-        for x in 0..BOARD_SIZE {
-            grid[1][x] = Some(Piece::new(PieceType::Pawn, PieceColor::Black));
-            grid[6][x] = Some(Piece::new(PieceType::Pawn, PieceColor::White));
-        }
+// Lo necesario para que unmake_move deje el tablero exactamente como estaba
+#[derive(Clone, Copy, Debug)]
+pub struct Undo {
+    mv: Move,
+    moved: Piece,                          // Pieza antes de mover (un peón si la jugada corona)
+    captured: Option<(usize, Piece)>,      // Casilla y pieza capturada (en la captura al paso no es el destino)
+    rook_move: Option<(usize, usize)>,     // Origen y destino de la Torre al enrocar
+    castling: CastlingRights,
+    en_passant: Option<(usize, usize)>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    hash: u64,
+}
 
-        // Configurar otras piezas
-        let layout = [
-            PieceType::Rook, PieceType::Knight, PieceType::Bishop, PieceType::Queen,
-            PieceType::King, PieceType::Bishop, PieceType::Knight, PieceType::Rook
-        ];
+// Estado que cambia al pasar el turno sin mover (jugada nula)
+#[derive(Clone, Copy, Debug)]
+pub struct NullMoveUndo {
+    en_passant: Option<(usize, usize)>,
+    halfmove_clock: u32,
+    hash: u64,
+}
+
+// Errores al interpretar una cadena FEN
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    WrongFieldCount(usize),
+    InvalidPlacement(String),
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+}
 
-        for (x, &piece_type) in layout.iter().enumerate() {
-            grid[0][x] = Some(Piece::new(piece_type, PieceColor::Black));
-            grid[7][x] = Some(Piece::new(piece_type, PieceColor::White));
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::WrongFieldCount(n) => write!(f, "FEN con {} campos (se esperaban entre 4 y 6)", n),
+            FenError::InvalidPlacement(msg) => write!(f, "Colocación de piezas inválida: {}", msg),
+            FenError::InvalidSideToMove(s) => write!(f, "Turno inválido '{}' (se esperaba 'w' o 'b')", s),
+            FenError::InvalidCastling(s) => write!(f, "Derechos de enroque inválidos '{}'", s),
+            FenError::InvalidEnPassant(s) => write!(f, "Casilla de captura al paso inválida '{}'", s),
+            FenError::InvalidHalfmoveClock(s) => write!(f, "Contador de medios movimientos inválido '{}'", s),
+            FenError::InvalidFullmoveNumber(s) => write!(f, "Número de movimiento inválido '{}'", s),
         }
+    }
+}
 
-        Self { grid, last_move: None }
+impl std::error::Error for FenError {}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    pub fn move_piece(&mut self, from: (usize, usize), to: (usize, usize)) {
-        if let Some(mut piece) = self.grid[from.1][from.0].take() {
-            let dx = (to.0 as i32 - from.0 as i32).abs();
-            let _dy = to.1 as i32 - from.1 as i32;
+impl Board {
+    pub fn new() -> Self {
+        Self::from_fen(START_FEN).expect("La FEN inicial es válida")
+    }
 
-            // Manejar ejecución de Enroque
-            if piece.piece_type == PieceType::King && dx == 2 { // dx is already abs()
-                let rook_x = if to.0 > from.0 { 7 } else { 0 };
-                let new_rook_x = if to.0 > from.0 { 5 } else { 3 };
-                let rook_y = from.1;
-                
-                if let Some(mut rook) = self.grid[rook_y][rook_x].take() {
-                     rook.has_moved = true;
-                     self.grid[rook_y][new_rook_x] = Some(rook);
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 4 || fields.len() > 6 {
+            return Err(FenError::WrongFieldCount(fields.len()));
+        }
+
+        // 1. Colocación de piezas (de la fila 8 a la 1)
+        let mut grid = [[None; BOARD_SIZE]; BOARD_SIZE];
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != BOARD_SIZE {
+            return Err(FenError::InvalidPlacement(format!("{} filas (se esperaban 8)", ranks.len())));
+        }
+        for (y, rank) in ranks.iter().enumerate() {
+            let mut x = 0;
+            for c in rank.chars() {
+                if let Some(skip) = c.to_digit(10) {
+                    if skip == 0 || skip > 8 {
+                        return Err(FenError::InvalidPlacement(format!("dígito '{}' fuera de rango", c)));
+                    }
+                    x += skip as usize;
+                } else {
+                    let piece = piece_from_fen_char(c).ok_or_else(|| {
+                        FenError::InvalidPlacement(format!("carácter de pieza desconocido '{}'", c))
+                    })?;
+                    if x >= BOARD_SIZE {
+                        return Err(FenError::InvalidPlacement(format!("la fila {} tiene más de 8 casillas", 8 - y)));
+                    }
+                    grid[y][x] = Some(piece);
+                    x += 1;
+                }
+                if x > BOARD_SIZE {
+                    return Err(FenError::InvalidPlacement(format!("la fila {} tiene más de 8 casillas", 8 - y)));
                 }
             }
+            if x != BOARD_SIZE {
+                return Err(FenError::InvalidPlacement(format!("la fila {} tiene {} casillas", 8 - y, x)));
+            }
+        }
 
-            // Manejar ejecución de En Passant
-            if piece.piece_type == PieceType::Pawn && dx.abs() == 1 && self.grid[to.1][to.0].is_none() {
-                // Si se mueve en diagonal a una casilla vacía, es en passant
-                // Eliminar el peón capturado (que está en [to.0, from.1])
-                self.grid[from.1][to.0] = None;
+        for color in [PieceColor::White, PieceColor::Black] {
+            let kings = grid.iter().flatten().flatten()
+                .filter(|p| p.piece_type == PieceType::King && p.color == color)
+                .count();
+            if kings != 1 {
+                return Err(FenError::InvalidPlacement(format!("{} reyes de color {:?}", kings, color)));
             }
+        }
+        if grid[0].iter().chain(grid[7].iter())
+            .any(|square| matches!(square, Some(Piece { piece_type: PieceType::Pawn, .. }))) {
+            return Err(FenError::InvalidPlacement("peón en la primera u octava fila".to_string()));
+        }
+
+        // 2. Turno
+        let side_to_move = match fields[1] {
+            "w" => PieceColor::White,
+            "b" => PieceColor::Black,
+            other => return Err(FenError::InvalidSideToMove(other.to_string())),
+        };
 
-            piece.has_moved = true;
-            
-            // Promoción (Básica: siempre Reina)
-            if piece.piece_type == PieceType::Pawn {
-                if (piece.color == PieceColor::White && to.1 == 0) ||
-                   (piece.color == PieceColor::Black && to.1 == 7) {
-                    piece.piece_type = PieceType::Queen;
+        // 3. Derechos de enroque
+        let mut castling = CastlingRights::none();
+        let castling_field = fields[2];
+        if castling_field != "-" {
+            if castling_field.is_empty() || castling_field.len() > 4 {
+                return Err(FenError::InvalidCastling(castling_field.to_string()));
+            }
+            for c in castling_field.chars() {
+                let (color, kingside) = match c {
+                    'K' => (PieceColor::White, true),
+                    'Q' => (PieceColor::White, false),
+                    'k' => (PieceColor::Black, true),
+                    'q' => (PieceColor::Black, false),
+                    _ => return Err(FenError::InvalidCastling(castling_field.to_string())),
+                };
+                if castling.get(color, kingside) {
+                    return Err(FenError::InvalidCastling(castling_field.to_string()));
+                }
+
+                // El Rey y la Torre deben seguir en sus casillas originales
+                let y = if color == PieceColor::White { 7 } else { 0 };
+                let rook_x = if kingside { 7 } else { 0 };
+                let in_place = |x: usize, piece_type: PieceType| {
+                    matches!(grid[y][x], Some(p) if p.piece_type == piece_type && p.color == color)
+                };
+                if !in_place(4, PieceType::King) || !in_place(rook_x, PieceType::Rook) {
+                    return Err(FenError::InvalidCastling(castling_field.to_string()));
                 }
+
+                castling.set(color, kingside, true);
             }
+        }
 
-            self.grid[to.1][to.0] = Some(piece);
-            self.last_move = Some((from, to));
+        // 4. Captura al paso
+        let mut en_passant = None;
+        if fields[3] != "-" {
+            let invalid = || FenError::InvalidEnPassant(fields[3].to_string());
+            let (x, y) = parse_square(fields[3]).ok_or_else(invalid)?;
+            // La casilla objetivo está detrás del peón que acaba de avanzar dos casillas
+            let (expected_y, pawn_y, from_y, pawn_color) = match side_to_move {
+                PieceColor::White => (2, 3, 1, PieceColor::Black),
+                PieceColor::Black => (5, 4, 6, PieceColor::White),
+            };
+            if y != expected_y || grid[y][x].is_some() || grid[from_y][x].is_some() {
+                return Err(invalid());
+            }
+            match grid[pawn_y][x] {
+                Some(Piece { piece_type: PieceType::Pawn, color, .. }) if color == pawn_color => {
+                    en_passant = Some((x, y));
+                }
+                _ => return Err(invalid()),
+            }
         }
-    }
 
-    pub fn get_valid_moves(&self, pos: (usize, usize)) -> Vec<(usize, usize)> {
-        let mut moves = Vec::new();
-        if let Some(piece) = self.grid[pos.1][pos.0] {
-            let candidate_moves = self.get_pseudo_legal_moves(pos, &piece);
-            
-            for &dest in &candidate_moves {
-                // Determinar si el movimiento pone/deja al propio Rey en jaque
-                let mut temp_board = self.clone();
-                temp_board.move_piece(pos, dest);
-                if !temp_board.is_in_check(piece.color) {
-                    moves.push(dest);
+        // 5. y 6. Contadores (opcionales, con los valores por defecto habituales)
+        let halfmove_clock = match fields.get(4) {
+            Some(s) => s.parse().map_err(|_| FenError::InvalidHalfmoveClock(s.to_string()))?,
+            None => 0,
+        };
+        let fullmove_number = match fields.get(5) {
+            Some(s) => match s.parse() {
+                Ok(n) if n >= 1 => n,
+                _ => return Err(FenError::InvalidFullmoveNumber(s.to_string())),
+            },
+            None => 1,
+        };
+
+        let mut board = Self {
+            squares: [None; 64],
+            pieces: [[0; 6]; 2],
+            occupancy: [0; 2],
+            side_to_move,
+            castling,
+            en_passant,
+            halfmove_clock,
+            fullmove_number,
+            hash: 0,
+        };
+        for (y, row) in grid.iter().enumerate() {
+            for (x, &piece) in row.iter().enumerate() {
+                if let Some(piece) = piece {
+                    board.put_piece(square((x, y)), piece);
                 }
             }
         }
-        moves
+        board.hash = board.compute_hash();
+        Ok(board)
     }
 
-    fn get_pseudo_legal_moves(&self, pos: (usize, usize), piece: &Piece) -> Vec<(usize, usize)> {
-        let mut moves = Vec::new();
-        let (x, y) = (pos.0 as i32, pos.1 as i32);
+    pub fn piece_at(&self, pos: (usize, usize)) -> Option<Piece> {
+        self.squares[square(pos)]
+    }
 
-        match piece.piece_type {
-            PieceType::Pawn => {
-                let direction = if piece.color == PieceColor::White { -1 } else { 1 };
-                
-                // Movimiento hacia adelante
-                let new_y = y + direction;
-                if self.is_valid_pos(x, new_y) && self.is_empty(x, new_y) {
-                    moves.push((x as usize, new_y as usize));
-                    
-                    // Movimiento doble
-                    if !piece.has_moved {
-                        let double_y = y + 2 * direction;
-                        if self.is_valid_pos(x, double_y) && self.is_empty(x, double_y) {
-                            moves.push((x as usize, double_y as usize));
-                        }
-                    }
-                }
+    pub(crate) fn pieces(&self, color: PieceColor, piece_type: PieceType) -> Bitboard {
+        self.pieces[color as usize][piece_type as usize]
+    }
 
-                // Capturas
-                for &dx in &[-1, 1] {
-                    let caps_x = x + dx;
-                    let caps_y = y + direction;
-                     
-                    // Captura normal
-                    if self.is_valid_pos(caps_x, caps_y) && self.is_enemy(caps_x, caps_y, piece.color) {
-                         moves.push((caps_x as usize, caps_y as usize));
-                    }
+    pub(crate) fn pieces_of(&self, color: PieceColor) -> Bitboard {
+        self.occupancy[color as usize]
+    }
 
-                    // En Passant
-                     if self.is_valid_pos(caps_x, caps_y) && self.is_empty(caps_x, caps_y) {
-                         if let Some(((_last_from_x, last_from_y), (last_to_x, last_to_y))) = self.last_move {
-                             // Verificar si el último movimiento fue un avance doble de peón adyacente
-                             if last_to_x == caps_x as usize && last_to_y == y as usize &&
-                                (last_from_y as i32 - last_to_y as i32).abs() == 2 {
-                                     // Verificar si la pieza allí es un peón (debería serlo)
-                                     if let Some(target) = self.grid[last_to_y][last_to_x] {
-                                         if target.piece_type == PieceType::Pawn && target.color != piece.color {
-                                             moves.push((caps_x as usize, caps_y as usize));
-                                         }
-                                     }
-                                }
-                         }
-                     }
-                }
+    pub(crate) fn occupied(&self) -> Bitboard {
+        self.occupancy[0] | self.occupancy[1]
+    }
+
+    // Coloca una pieza en una casilla vacía, manteniendo bitboards y hash al día
+    fn put_piece(&mut self, sq: usize, piece: Piece) {
+        self.squares[sq] = Some(piece);
+        self.pieces[piece.color as usize][piece.piece_type as usize] |= bit(sq);
+        self.occupancy[piece.color as usize] |= bit(sq);
+        self.hash ^= zobrist::piece_key(piece.color, piece.piece_type, sq);
+    }
+
+    fn remove_piece(&mut self, sq: usize) -> Option<Piece> {
+        let piece = self.squares[sq].take()?;
+        self.pieces[piece.color as usize][piece.piece_type as usize] &= !bit(sq);
+        self.occupancy[piece.color as usize] &= !bit(sq);
+        self.hash ^= zobrist::piece_key(piece.color, piece.piece_type, sq);
+        Some(piece)
+    }
+
+    // Hash Zobrist calculado desde cero (move_piece lo mantiene de forma incremental)
+    pub fn compute_hash(&self) -> u64 {
+        let mut hash = self.state_key();
+        for sq in squares(self.occupied()) {
+            if let Some(piece) = self.squares[sq] {
+                hash ^= zobrist::piece_key(piece.color, piece.piece_type, sq);
             }
-            PieceType::Rook => self.sliding_moves(&mut moves, x, y, &[(0, 1), (0, -1), (1, 0), (-1, 0)], piece.color),
-            PieceType::Bishop => self.sliding_moves(&mut moves, x, y, &[(1, 1), (1, -1), (-1, 1), (-1, -1)], piece.color),
-            PieceType::Queen => {
-                self.sliding_moves(&mut moves, x, y, &[(0, 1), (0, -1), (1, 0), (-1, 0)], piece.color);
-                self.sliding_moves(&mut moves, x, y, &[(1, 1), (1, -1), (-1, 1), (-1, -1)], piece.color);
+        }
+        hash
+    }
+
+    // Parte del hash que no depende de las piezas: turno, enroques y captura al paso
+    fn state_key(&self) -> u64 {
+        let mut key = zobrist::castling_key(self.castling.bits());
+        if self.side_to_move == PieceColor::Black {
+            key ^= zobrist::side_key();
+        }
+        // La casilla al paso solo cuenta si algún peón puede capturar realmente
+        if let Some(ep) = self.en_passant {
+            // Un peón propio la ataca si está donde atacaría un peón rival situado en ella
+            let attackers = bitboard::pawn_attacks(self.side_to_move.opposite(), square(ep));
+            if attackers & self.pieces(self.side_to_move, PieceType::Pawn) != 0 {
+                key ^= zobrist::en_passant_key(ep.0);
             }
-            PieceType::Knight => {
-                let offsets = [
-                    (1, 2), (1, -2), (-1, 2), (-1, -2),
-                    (2, 1), (2, -1), (-2, 1), (-2, -1)
-                ];
-                for &(dx, dy) in &offsets {
-                    if self.is_valid_pos(x + dx, y + dy) {
-                        if self.is_empty(x + dx, y + dy) || self.is_enemy(x + dx, y + dy, piece.color) {
-                            moves.push(((x + dx) as usize, (y + dy) as usize));
+        }
+        key
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for y in 0..BOARD_SIZE {
+            let mut empty = 0;
+            for x in 0..BOARD_SIZE {
+                match self.piece_at((x, y)) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
                         }
+                        fen.push(piece_to_fen_char(&piece));
                     }
+                    None => empty += 1,
                 }
             }
-            PieceType::King => {
-                let offsets = [
-                    (0, 1), (0, -1), (1, 0), (-1, 0),
-                    (1, 1), (1, -1), (-1, 1), (-1, -1)
-                ];
-                for &(dx, dy) in &offsets {
-                    if self.is_valid_pos(x + dx, y + dy) {
-                        if self.is_empty(x + dx, y + dy) || self.is_enemy(x + dx, y + dy, piece.color) {
-                            moves.push(((x + dx) as usize, (y + dy) as usize));
-                        }
-                    }
-                }
-
-                // Enroque
-                if !piece.has_moved {
-                    // Lado del Rey
-                    if self.can_castle(pos, true) {
-                        moves.push(((x + 2) as usize, y as usize));
-                    }
-                    // Lado de la Reina
-                    if self.can_castle(pos, false) {
-                        moves.push(((x - 2) as usize, y as usize));
-                    }
-                }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if y < BOARD_SIZE - 1 {
+                fen.push('/');
             }
         }
 
-        moves
-    }
+        fen.push_str(match self.side_to_move {
+            PieceColor::White => " w ",
+            PieceColor::Black => " b ",
+        });
 
-    fn sliding_moves(&self, moves: &mut Vec<(usize, usize)>, x: i32, y: i32, dirs: &[(i32, i32)], color: PieceColor) {
-        for &(dx, dy) in dirs {
-            let mut nx = x + dx;
-            let mut ny = y + dy;
-            while self.is_valid_pos(nx, ny) {
-                if self.is_empty(nx, ny) {
-                    moves.push((nx as usize, ny as usize));
-                } else {
-                    if self.is_enemy(nx, ny, color) {
-                        moves.push((nx as usize, ny as usize));
-                    }
-                    break;
-                }
-                nx += dx;
-                ny += dy;
+        let mut castling = String::new();
+        for (c, color, kingside) in [
+            ('K', PieceColor::White, true),
+            ('Q', PieceColor::White, false),
+            ('k', PieceColor::Black, true),
+            ('q', PieceColor::Black, false),
+        ] {
+            if self.castling.get(color, kingside) {
+                castling.push(c);
             }
         }
-    }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        fen.push_str(&castling);
 
-    fn is_valid_pos(&self, x: i32, y: i32) -> bool {
-        x >= 0 && x < 8 && y >= 0 && y < 8
+        fen.push(' ');
+        match self.en_passant {
+            Some(square) => fen.push_str(&square_name(square)),
+            None => fen.push('-'),
+        }
+
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.fullmove_number));
+        fen
     }
 
-    fn is_empty(&self, x: i32, y: i32) -> bool {
-        self.grid[y as usize][x as usize].is_none()
+    // Aplica una jugada solo si es legal (p. ej. una recibida por red) y devuelve la que se jugó,
+    // con las marcas de captura, enroque y al paso del propio generador; None si no lo es
+    pub fn move_piece(&mut self, mv: Move) -> Option<Move> {
+        let legal = self.generate_legal_moves()
+            .into_iter()
+            .find(|m| m.from == mv.from && m.to == mv.to && m.promotion == mv.promotion)?;
+        self.make_move(legal);
+        Some(legal)
     }
 
-    fn is_enemy(&self, x: i32, y: i32, color: PieceColor) -> bool {
-        if let Some(piece) = self.grid[y as usize][x as usize] {
-            piece.color != color
+    // Aplica una jugada y devuelve lo necesario para deshacerla con unmake_move
+    pub fn make_move(&mut self, mv: Move) -> Undo {
+        let (from, to) = (mv.from, mv.to);
+        let (castling, en_passant) = (self.castling, self.en_passant);
+        let (halfmove_clock, fullmove_number, hash) = (self.halfmove_clock, self.fullmove_number, self.hash);
+
+        // Se calcula antes de levantar la pieza: la captura al paso depende de ella
+        let old_state_key = self.state_key();
+        // put_piece y remove_piece actualizan el hash de las piezas; aquí solo se cambia el estado
+        let moved = self.remove_piece(square(from)).expect("No hay ninguna pieza en la casilla de origen");
+        let mut piece = moved;
+        let mut captured = self.remove_piece(square(to)).map(|p| (square(to), p));
+        let is_capture = captured.is_some();
+        let is_pawn_move = piece.piece_type == PieceType::Pawn;
+        let mut rook_move = None;
+        self.hash ^= old_state_key;
+
+        // Manejar ejecución de Enroque
+        if piece.piece_type == PieceType::King && from.0.abs_diff(to.0) == 2 {
+            let rook_x = if to.0 > from.0 { 7 } else { 0 };
+            let new_rook_x = if to.0 > from.0 { 5 } else { 3 };
+            let (rook_from, rook_to) = (square((rook_x, from.1)), square((new_rook_x, from.1)));
+            if let Some(rook) = self.remove_piece(rook_from) {
+                self.put_piece(rook_to, rook);
+                rook_move = Some((rook_from, rook_to));
+            }
+        }
+
+        // Manejar ejecución de En Passant: el peón capturado está en [to.0, from.1]
+        if is_pawn_move && self.en_passant == Some(to) {
+            let captured_square = square((to.0, from.1));
+            captured = self.remove_piece(captured_square).map(|p| (captured_square, p));
+        }
+
+        // Actualizar derechos de enroque
+        if piece.piece_type == PieceType::King {
+            self.castling.set(piece.color, true, false);
+            self.castling.set(piece.color, false, false);
+        }
+        self.castling.remove_for_square(from);
+        self.castling.remove_for_square(to);
+
+        // Un avance doble deja la casilla saltada disponible para captura al paso
+        self.en_passant = if is_pawn_move && from.1.abs_diff(to.1) == 2 {
+            Some((from.0, (from.1 + to.1) / 2))
+        } else {
+            None
+        };
+
+        // Promoción a la pieza elegida (Reina si no se indicó ninguna)
+        if is_pawn_move && (to.1 == 0 || to.1 == 7) {
+            piece.piece_type = mv.promotion.unwrap_or(PieceType::Queen);
+        }
+
+        // Actualizar contadores y turno
+        if is_pawn_move || is_capture {
+            self.halfmove_clock = 0;
         } else {
-            false
+            self.halfmove_clock += 1;
+        }
+        if piece.color == PieceColor::Black {
+            self.fullmove_number += 1;
         }
+        self.side_to_move = piece.color.opposite();
+
+        self.put_piece(square(to), piece);
+        self.hash ^= self.state_key();
+
+        Undo { mv, moved, captured, rook_move, castling, en_passant, halfmove_clock, fullmove_number, hash }
     }
 
-    pub fn is_in_check(&self, color: PieceColor) -> bool {
-        let king_pos = self.find_king(color);
-        if let Some((kx, ky)) = king_pos {
-            self.is_square_attacked((kx, ky), color)
-        } else {
-            false
+    // Deshace la última jugada aplicada con make_move
+    pub fn unmake_move(&mut self, undo: Undo) {
+        self.remove_piece(square(undo.mv.to));
+        self.put_piece(square(undo.mv.from), undo.moved);
+        if let Some((rook_from, rook_to)) = undo.rook_move
+            && let Some(rook) = self.remove_piece(rook_to) {
+            self.put_piece(rook_from, rook);
         }
+        if let Some((captured_square, captured)) = undo.captured {
+            self.put_piece(captured_square, captured);
+        }
+
+        self.side_to_move = undo.moved.color;
+        self.castling = undo.castling;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+        self.hash = undo.hash;
     }
 
-    // Ayudante para verificar si una casilla es atacada por el enemigo
-    fn is_square_attacked(&self, pos: (usize, usize), color: PieceColor) -> bool {
-         for y in 0..BOARD_SIZE {
-            for x in 0..BOARD_SIZE {
-                if let Some(piece) = self.grid[y][x] {
-                    if piece.color != color {
-                         let moves = self.get_basic_attacks((x, y), &piece);
-                         if moves.contains(&pos) {
-                             return true;
-                         }
+    // Pasa el turno sin mover ninguna pieza, para la poda de jugada nula de la IA.
+    // No es una jugada legal: no debe hacerse estando en jaque. El contador de medios movimientos
+    // vuelve a cero para que la detección de repeticiones no cruce la jugada nula.
+    pub fn make_null_move(&mut self) -> NullMoveUndo {
+        let undo = NullMoveUndo { en_passant: self.en_passant, halfmove_clock: self.halfmove_clock, hash: self.hash };
+        self.hash ^= self.state_key();
+        self.en_passant = None;
+        self.halfmove_clock = 0;
+        self.side_to_move = self.side_to_move.opposite();
+        self.hash ^= self.state_key();
+        undo
+    }
+
+    pub fn unmake_null_move(&mut self, undo: NullMoveUndo) {
+        self.side_to_move = self.side_to_move.opposite();
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.hash = undo.hash;
+    }
+
+    // Si el bando tiene alguna pieza aparte de Rey y peones (sin ellas el zugzwang es habitual)
+    pub fn has_non_pawn_material(&self, color: PieceColor) -> bool {
+        let pawns_and_king = self.pieces(color, PieceType::Pawn) | self.pieces(color, PieceType::King);
+        self.occupancy[color as usize] & !pawns_and_king != 0
+    }
+
+    // El tablero se modifica para probar cada jugada, pero queda como estaba
+    pub fn get_valid_moves(&mut self, pos: (usize, usize)) -> Vec<Move> {
+        self.legal_moves_within(pos, !0)
+    }
+
+    // Jugadas legales de la pieza en `pos` cuyo destino está en `mask`
+    fn legal_moves_within(&mut self, pos: (usize, usize), mask: Bitboard) -> Vec<Move> {
+        let mut moves = Vec::new();
+        if let Some(piece) = self.piece_at(pos) {
+            let targets = self.pseudo_legal_targets(pos, &piece) & mask;
+
+            for dest in squares(targets).map(position) {
+                let mv = self.describe_move(pos, dest, &piece);
+
+                // Determinar si el movimiento pone/deja al propio Rey en jaque
+                let undo = self.make_move(mv);
+                let leaves_king_in_check = self.is_in_check(piece.color);
+                self.unmake_move(undo);
+                if leaves_king_in_check {
+                    continue;
+                }
+
+                // Una coronación genera una jugada por cada pieza posible
+                if piece.piece_type == PieceType::Pawn && (dest.1 == 0 || dest.1 == 7) {
+                    for &promotion in &PROMOTION_PIECES {
+                        moves.push(Move { promotion: Some(promotion), ..mv });
                     }
+                } else {
+                    moves.push(mv);
                 }
             }
         }
-        false
+        moves
     }
-    
-    // Generación de movimientos simplificada que no verifica jaque/enroque/cosas especiales, solo ataques puros.
-    fn get_basic_attacks(&self, pos: (usize, usize), piece: &Piece) -> Vec<(usize, usize)> {
+
+    // Todas las jugadas legales del bando al que le toca mover
+    pub fn generate_legal_moves(&mut self) -> Vec<Move> {
         let mut moves = Vec::new();
-        let (x, y) = (pos.0 as i32, pos.1 as i32);
+        for sq in squares(self.occupancy[self.side_to_move as usize]) {
+            moves.extend(self.get_valid_moves(position(sq)));
+        }
+        moves
+    }
+
+    // Solo las capturas (al paso incluidas) y las coronaciones legales, para la búsqueda de quietud
+    pub fn generate_legal_captures(&mut self) -> Vec<Move> {
+        let color = self.side_to_move;
+        let enemies = self.occupancy[color.opposite() as usize];
+        // Un peón solo llega a la primera o la última fila coronando
+        let mut pawn_mask = enemies | 0xFF00_0000_0000_00FF;
+        if let Some(ep) = self.en_passant {
+            pawn_mask |= bit(square(ep));
+        }
+        let mut moves = Vec::new();
+        for sq in squares(self.occupancy[color as usize]) {
+            let mask = if self.pieces(color, PieceType::Pawn) & bit(sq) != 0 { pawn_mask } else { enemies };
+            moves.extend(self.legal_moves_within(position(sq), mask));
+        }
+        moves
+    }
+
+    // Completa las banderas de una jugada a partir del estado actual
+    fn describe_move(&self, from: (usize, usize), to: (usize, usize), piece: &Piece) -> Move {
+        let is_en_passant = piece.piece_type == PieceType::Pawn && self.en_passant == Some(to);
+        Move {
+            is_capture: self.piece_at(to).is_some() || is_en_passant,
+            is_castle: piece.piece_type == PieceType::King && from.0.abs_diff(to.0) == 2,
+            is_en_passant,
+            ..Move::new(from, to)
+        }
+    }
+
+    // Casillas de destino sin comprobar si el propio Rey queda en jaque
+    fn pseudo_legal_targets(&self, pos: (usize, usize), piece: &Piece) -> Bitboard {
+        let sq = square(pos);
+        let own = self.occupancy[piece.color as usize];
+        let enemy = self.occupancy[piece.color.opposite() as usize];
+        let occupied = own | enemy;
 
         match piece.piece_type {
             PieceType::Pawn => {
-                let direction = if piece.color == PieceColor::White { -1 } else { 1 };
-                // Los peones solo atacan diagonalmente
-                for &dx in &[-1, 1] {
-                    let caps_x = x + dx;
-                    let caps_y = y + direction;
-                     if self.is_valid_pos(caps_x, caps_y) {
-                         moves.push((caps_x as usize, caps_y as usize));
-                     }
+                // Capturas, incluida la captura al paso
+                let mut capturable = enemy;
+                if let Some(ep) = self.en_passant {
+                    capturable |= bit(square(ep));
                 }
-            }
-            PieceType::King => {
-                 let offsets = [
-                    (0, 1), (0, -1), (1, 0), (-1, 0),
-                    (1, 1), (1, -1), (-1, 1), (-1, -1)
-                ];
-                for &(dx, dy) in &offsets {
-                    if self.is_valid_pos(x + dx, y + dy) {
-                        moves.push(((x + dx) as usize, (y + dy) as usize));
+                let mut targets = bitboard::pawn_attacks(piece.color, sq) & capturable;
+
+                // Movimiento hacia adelante (nunca hay peones en la primera ni en la octava fila)
+                let (start_y, forward_y) = if piece.color == PieceColor::White {
+                    (6, pos.1 - 1)
+                } else {
+                    (1, pos.1 + 1)
+                };
+                let forward = square((pos.0, forward_y));
+                if occupied & bit(forward) == 0 {
+                    targets |= bit(forward);
+
+                    // Movimiento doble desde la fila inicial
+                    if pos.1 == start_y {
+                        let double = square((pos.0, 2 * forward_y - pos.1));
+                        if occupied & bit(double) == 0 {
+                            targets |= bit(double);
+                        }
                     }
                 }
+                targets
             }
-            // Otros son iguales a lo normal
-            PieceType::Rook => self.sliding_moves(&mut moves, x, y, &[(0, 1), (0, -1), (1, 0), (-1, 0)], piece.color),
-            PieceType::Bishop => self.sliding_moves(&mut moves, x, y, &[(1, 1), (1, -1), (-1, 1), (-1, -1)], piece.color),
+            PieceType::Rook => bitboard::rook_attacks(sq, occupied) & !own,
+            PieceType::Bishop => bitboard::bishop_attacks(sq, occupied) & !own,
             PieceType::Queen => {
-                self.sliding_moves(&mut moves, x, y, &[(0, 1), (0, -1), (1, 0), (-1, 0)], piece.color);
-                self.sliding_moves(&mut moves, x, y, &[(1, 1), (1, -1), (-1, 1), (-1, -1)], piece.color);
+                (bitboard::rook_attacks(sq, occupied) | bitboard::bishop_attacks(sq, occupied)) & !own
             }
-            PieceType::Knight => {
-                let offsets = [
-                    (1, 2), (1, -2), (-1, 2), (-1, -2),
-                    (2, 1), (2, -1), (-2, 1), (-2, -1)
-                ];
-                for &(dx, dy) in &offsets {
-                    if self.is_valid_pos(x + dx, y + dy) {
-                        moves.push(((x + dx) as usize, (y + dy) as usize));
-                    }
+            PieceType::Knight => bitboard::knight_attacks(sq) & !own,
+            PieceType::King => {
+                let mut targets = bitboard::king_attacks(sq) & !own;
+
+                // Enroque
+                // Lado del Rey
+                if self.can_castle(pos, piece.color, true) {
+                    targets |= bit(sq + 2);
+                }
+                // Lado de la Reina
+                if self.can_castle(pos, piece.color, false) {
+                    targets |= bit(sq - 2);
                 }
+                targets
             }
         }
-        moves
     }
 
-    fn can_castle(&self, king_pos: (usize, usize), kingside: bool) -> bool {
-        if self.is_in_check(self.grid[king_pos.1][king_pos.0].unwrap().color) {
+    pub fn is_in_check(&self, color: PieceColor) -> bool {
+        match self.find_king(color) {
+            Some(king_pos) => self.is_square_attacked(king_pos, color),
+            None => false,
+        }
+    }
+
+    // Ayudante para verificar si una casilla es atacada por el enemigo.
+    // Se miran los ataques desde la propia casilla: si un Caballo situado en ella
+    // alcanzaría un Caballo enemigo, ese Caballo también la ataca (y lo mismo con el resto).
+    fn is_square_attacked(&self, pos: (usize, usize), color: PieceColor) -> bool {
+        let sq = square(pos);
+        let enemy = color.opposite();
+        let occupied = self.occupied();
+        let queens = self.pieces(enemy, PieceType::Queen);
+
+        (bitboard::pawn_attacks(color, sq) & self.pieces(enemy, PieceType::Pawn)) != 0
+            || (bitboard::knight_attacks(sq) & self.pieces(enemy, PieceType::Knight)) != 0
+            || (bitboard::king_attacks(sq) & self.pieces(enemy, PieceType::King)) != 0
+            || (bitboard::bishop_attacks(sq, occupied) & (self.pieces(enemy, PieceType::Bishop) | queens)) != 0
+            || (bitboard::rook_attacks(sq, occupied) & (self.pieces(enemy, PieceType::Rook) | queens)) != 0
+    }
+
+    fn can_castle(&self, king_pos: (usize, usize), color: PieceColor, kingside: bool) -> bool {
+        // El derecho se pierde en cuanto se mueven el Rey o esa Torre
+        if !self.castling.get(color, kingside) {
             return false;
         }
 
-        let y = king_pos.1;
-        let _x = king_pos.0;
-        let color = self.grid[king_pos.1][king_pos.0].unwrap().color;
+        let y = if color == PieceColor::White { 7 } else { 0 };
+        if king_pos != (4, y) || self.is_in_check(color) {
+            return false;
+        }
 
         let (rook_x, empty_x_range) = if kingside {
             (7, 5..=6)
@@ -313,18 +700,14 @@ impl Board {
             (0, 1..=3)
         };
 
-        // Verificar si la Torre está allí y no se ha movido
-        if let Some(piece) = self.grid[y][rook_x] {
-            if piece.piece_type != PieceType::Rook || piece.has_moved || piece.color != color {
-                return false;
-            }
-        } else {
+        // Verificar que la Torre sigue allí
+        if !matches!(self.piece_at((rook_x, y)), Some(p) if p.piece_type == PieceType::Rook && p.color == color) {
             return false;
         }
 
         // Verificar que el camino esté despejado
         for check_x in empty_x_range {
-            if !self.grid[y][check_x].is_none() {
+            if self.piece_at((check_x, y)).is_some() {
                 return false;
             }
         }
@@ -346,35 +729,342 @@ impl Board {
     }
 
     
-    pub fn is_checkmate(&self, color: PieceColor) -> bool {
+    pub fn is_checkmate(&mut self, color: PieceColor) -> bool {
         if !self.is_in_check(color) {
             return false;
         }
         // Probar todos los movimientos para todas las piezas
-        for y in 0..BOARD_SIZE {
-            for x in 0..BOARD_SIZE {
-                if let Some(piece) = self.grid[y][x] {
-                    if piece.color == color {
-                         if !self.get_valid_moves((x, y)).is_empty() {
-                             return false;
-                         }
-                    }
-                }
-            }
+        !self.has_any_move(color)
+    }
+
+    pub fn is_stalemate(&mut self, color: PieceColor) -> bool {
+        if self.is_in_check(color) {
+            return false;
+        }
+        !self.has_any_move(color)
+    }
+
+    fn has_any_move(&mut self, color: PieceColor) -> bool {
+        squares(self.occupancy[color as usize])
+            .any(|sq| !self.get_valid_moves(position(sq)).is_empty())
+    }
+
+    // Veces que la posición actual aparece en el historial de hashes (que debe terminar en ella).
+    // Solo se mira desde la última captura o avance de peón, antes no puede repetirse.
+    pub fn repetition_count(&self, history: &[u64]) -> usize {
+        history.iter()
+            .rev()
+            .take(self.halfmove_clock as usize + 1)
+            .filter(|&&hash| hash == self.hash)
+            .count()
+    }
+
+    // 50 movimientos de cada bando sin capturas ni avances de peón: tablas reclamables
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock >= 100
+    }
+
+    // 75 movimientos de cada bando: tablas automáticas
+    pub fn is_seventy_five_move_draw(&self) -> bool {
+        self.halfmove_clock >= 150
+    }
+
+    // Posición muerta: ningún bando puede dar mate (R vs R, R+pieza menor vs R, alfiles del mismo color)
+    pub fn is_insufficient_material(&self) -> bool {
+        let both = |piece_type| {
+            self.pieces(PieceColor::White, piece_type) | self.pieces(PieceColor::Black, piece_type)
+        };
+        if both(PieceType::Pawn) | both(PieceType::Rook) | both(PieceType::Queen) != 0 {
+            return false;
+        }
+
+        let knights = both(PieceType::Knight).count_ones();
+        let bishops = both(PieceType::Bishop);
+        match (knights, bishops.count_ones()) {
+            (0, 0) | (1, 0) => true,
+            (0, _) => bishops & bitboard::LIGHT_SQUARES == 0 || bishops & !bitboard::LIGHT_SQUARES == 0,
+            _ => false,
+        }
+    }
+
+    // Resultado de la partida si el bando al que le toca mover ya no puede jugar
+    // o si ya no queda material para dar mate
+    pub fn outcome(&mut self) -> Option<GameResult> {
+        let color = self.side_to_move;
+        if self.is_checkmate(color) {
+            Some(GameResult::win_for(color.opposite(), EndReason::Checkmate))
+        } else if self.is_stalemate(color) {
+            Some(GameResult::Draw(EndReason::Stalemate))
+        } else if self.is_insufficient_material() {
+            Some(GameResult::Draw(EndReason::InsufficientMaterial))
+        } else {
+            None
         }
-        true
     }
 
     fn find_king(&self, color: PieceColor) -> Option<(usize, usize)> {
-        for y in 0..BOARD_SIZE {
-            for x in 0..BOARD_SIZE {
-                if let Some(piece) = self.grid[y][x] {
-                    if piece.piece_type == PieceType::King && piece.color == color {
-                        return Some((x, y));
-                    }
-                }
+        squares(self.pieces(color, PieceType::King)).next().map(position)
+    }
+}
+
+fn piece_from_fen_char(c: char) -> Option<Piece> {
+    let color = if c.is_ascii_uppercase() { PieceColor::White } else { PieceColor::Black };
+    let piece_type = match c.to_ascii_lowercase() {
+        'p' => PieceType::Pawn,
+        'n' => PieceType::Knight,
+        'b' => PieceType::Bishop,
+        'r' => PieceType::Rook,
+        'q' => PieceType::Queen,
+        'k' => PieceType::King,
+        _ => return None,
+    };
+    Some(Piece::new(piece_type, color))
+}
+
+fn piece_to_fen_char(piece: &Piece) -> char {
+    let c = match piece.piece_type {
+        PieceType::Pawn => 'p',
+        PieceType::Knight => 'n',
+        PieceType::Bishop => 'b',
+        PieceType::Rook => 'r',
+        PieceType::Queen => 'q',
+        PieceType::King => 'k',
+    };
+    if piece.color == PieceColor::White { c.to_ascii_uppercase() } else { c }
+}
+
+// Convierte "e3" en coordenadas (x, y) del tablero (y = 0 es la fila 8)
+pub fn parse_square(name: &str) -> Option<(usize, usize)> {
+    let bytes = name.as_bytes();
+    if bytes.len() != 2 {
+        return None;
+    }
+    let (file, rank) = (bytes[0], bytes[1]);
+    if !(b'a'..=b'h').contains(&file) || !(b'1'..=b'8').contains(&rank) {
+        return None;
+    }
+    Some(((file - b'a') as usize, (b'8' - rank) as usize))
+}
+
+pub fn square_name(pos: (usize, usize)) -> String {
+    format!("{}{}", (b'a' + pos.0 as u8) as char, 8 - pos.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    // Juega las jugadas en notación de coordenadas y devuelve el historial de hashes
+    fn play(b: &mut Board, moves: &str) -> Vec<u64> {
+        let mut history = vec![b.hash];
+        for text in moves.split_whitespace() {
+            let mv = b.generate_legal_moves().into_iter().find(|mv| mv.to_string() == text).unwrap();
+            b.make_move(mv);
+            history.push(b.hash);
+        }
+        history
+    }
+
+    #[test]
+    fn fen_round_trip() {
+        let fens = [
+            START_FEN,
+            // Kiwipete
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3",
+            "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 3 20",
+        ];
+        for fen in fens {
+            assert_eq!(board(fen).to_fen(), fen);
+        }
+        assert_eq!(Board::new().to_fen(), START_FEN);
+        assert_eq!(board(fens[2]).en_passant, Some((3, 2)));
+        let castling = board(fens[3]).castling;
+        assert!(castling.white_kingside && !castling.white_queenside);
+        assert!(!castling.black_kingside && castling.black_queenside);
+
+        // Los contadores son opcionales
+        assert_eq!(board("4k3/8/8/8/8/8/8/4K3 w - -").to_fen(), "4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+    }
+
+    // Comprueba a `depth` medios movimientos que el hash incremental coincide con el calculado
+    // y que deshacer cada jugada (también la nula) devuelve la posición y el hash de antes
+    fn check_hash(b: &mut Board, depth: u32) {
+        for mv in b.generate_legal_moves() {
+            let before = (b.to_fen(), b.hash);
+            let undo = b.make_move(mv);
+            assert_eq!(b.hash, b.compute_hash(), "{} {:?}", before.0, mv);
+            if depth > 1 {
+                check_hash(b, depth - 1);
             }
+            b.unmake_move(undo);
+            assert_eq!((b.to_fen(), b.hash), before);
+        }
+
+        // La jugada nula cambia el turno y borra el al paso
+        let before = (b.to_fen(), b.hash);
+        let undo = b.make_null_move();
+        assert_eq!(b.hash, b.compute_hash(), "{} nula", before.0);
+        b.unmake_null_move(undo);
+        assert_eq!((b.to_fen(), b.hash), before);
+    }
+
+    #[test]
+    fn incremental_hash() {
+        // Enroques, capturas, avances dobles y al paso, y coronaciones de los dos bandos
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "1r2k3/P1P5/8/3pP3/8/8/8/4K3 w - d6 0 1",
+            "4k3/8/8/8/2pP4/8/p6p/1N2K1R1 b - d3 0 1",
+        ] {
+            check_hash(&mut board(fen), 2);
+        }
+
+        // El mismo hash por distintos caminos, y distinto si cambia el turno o el al paso
+        let mut a = Board::new();
+        let mut b = Board::new();
+        for (from, to) in [((6, 7), (5, 5)), ((6, 0), (5, 2)), ((1, 7), (2, 5))] {
+            a.move_piece(Move::new(from, to));
+        }
+        for (from, to) in [((1, 7), (2, 5)), ((6, 0), (5, 2)), ((6, 7), (5, 5))] {
+            b.move_piece(Move::new(from, to));
+        }
+        assert_eq!(a.hash, b.hash);
+        assert_ne!(board("4k3/8/8/8/8/8/8/4K3 w - - 0 1").hash, board("4k3/8/8/8/8/8/8/4K3 b - - 0 1").hash);
+        assert_ne!(
+            board("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").hash,
+            board("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1").hash
+        );
+    }
+
+    #[test]
+    fn fen_errors() {
+        let error = |fen: &str| Board::from_fen(fen).unwrap_err();
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w"), FenError::WrongFieldCount(2));
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w - - 0 1 x"), FenError::WrongFieldCount(7));
+        assert!(matches!(error("4k3/8/8/8/8/8/8/4X3 w - - 0 1"), FenError::InvalidPlacement(_)));
+        assert!(matches!(error("4k3/8/8/8/8/8/7/4K3 w - - 0 1"), FenError::InvalidPlacement(_)));
+        assert!(matches!(error("4k3/8/8/8/8/8/9/4K3 w - - 0 1"), FenError::InvalidPlacement(_)));
+        assert!(matches!(error("4k3/8/8/8/8/8/8/p3K3 w - - 0 1"), FenError::InvalidPlacement(_)));
+        assert!(matches!(error("4k3/8/8/8/8/8/8/4K3 x - - 0 1"), FenError::InvalidSideToMove(_)));
+        assert!(matches!(error("4k3/8/8/8/8/8/8/4K3 w KX - 0 1"), FenError::InvalidCastling(_)));
+        // Derecho de enroque sin la torre en su casilla
+        assert!(matches!(error("4k3/8/8/8/8/8/8/4K3 w K - 0 1"), FenError::InvalidCastling(_)));
+        assert!(matches!(error("4k3/8/8/8/8/8/8/4K3 w - e9 0 1"), FenError::InvalidEnPassant(_)));
+        // Casilla bien escrita pero sin un peón que acabe de avanzar dos
+        assert!(matches!(error("4k3/8/8/8/8/8/8/4K3 w - e6 0 1"), FenError::InvalidEnPassant(_)));
+        assert!(matches!(error("4k3/8/8/8/8/8/8/4K3 w - - x 1"), FenError::InvalidHalfmoveClock(_)));
+        assert!(matches!(error("4k3/8/8/8/8/8/8/4K3 w - - 0 x"), FenError::InvalidFullmoveNumber(_)));
+        assert!(matches!(error("4k3/8/8/8/8/8/8/4K3 w - - 0 0"), FenError::InvalidFullmoveNumber(_)));
+    }
+
+    #[test]
+    fn move_piece_checks_legality() {
+        let mut b = Board::new();
+        let before = b.to_fen();
+        // Casilla de origen vacía, pieza que no puede ir ahí y jugada fuera de turno
+        assert!(b.move_piece(Move::new((4, 4), (4, 3))).is_none());
+        assert!(b.move_piece(Move::new((6, 7), (6, 5))).is_none());
+        assert!(b.move_piece(Move::new((4, 1), (4, 3))).is_none());
+        assert_eq!(b.to_fen(), before);
+
+        // Las marcas de la jugada recibida no cuentan: se usan las del generador
+        let mut b = board("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+        let played = b.move_piece(Move::new((4, 3), (3, 2))).unwrap();
+        assert!(played.is_en_passant && played.is_capture);
+        assert_eq!(b.to_fen(), "4k3/8/3P4/8/8/8/8/4K3 b - - 0 1");
+    }
+
+    #[test]
+    fn insufficient_material() {
+        let dead = [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",     // R vs R
+            "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1",   // R+A vs R
+            "4k3/8/8/8/8/8/8/1N2K3 b - - 0 1",   // R+C vs R
+            "2b1k3/8/8/8/8/8/8/3BK3 w - - 0 1",  // alfiles de casillas claras
+            "4k3/8/8/8/8/8/8/B1B1K3 w - - 0 1",  // dos alfiles de casillas oscuras
+        ];
+        for fen in dead {
+            assert!(board(fen).is_insufficient_material(), "{}", fen);
         }
-        None
+        let alive = [
+            "3bk3/8/8/8/8/8/8/3BK3 w - - 0 1",   // alfiles de distinto color
+            "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1",  // dos caballos: hay mates (con ayuda)
+            "4k3/8/8/8/8/8/8/1NB1K3 w - - 0 1",  // alfil y caballo
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",   // un peón
+            "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",    // una torre
+        ];
+        for fen in alive {
+            assert!(!board(fen).is_insufficient_material(), "{}", fen);
+        }
+
+        let mut b = board("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(b.outcome(), Some(GameResult::Draw(EndReason::InsufficientMaterial)));
+    }
+
+    #[test]
+    fn legal_captures() {
+        // Kiwipete, y una posición con al paso y coronaciones con y sin captura
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "1r2k3/P1P5/8/3pP3/8/8/8/4K3 w - d6 0 1",
+            "4k3/8/8/8/8/8/p6p/1N2K1R1 b - - 0 1",
+        ] {
+            let mut b = board(fen);
+            let mut expected: Vec<String> = b.generate_legal_moves().into_iter()
+                .filter(|mv| mv.is_capture || mv.promotion.is_some())
+                .map(|mv| mv.to_string())
+                .collect();
+            let mut captures: Vec<String> = b.generate_legal_captures().into_iter().map(|mv| mv.to_string()).collect();
+            expected.sort();
+            captures.sort();
+            assert_eq!(captures, expected, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn move_rules() {
+        let b = board("4k3/8/8/8/8/8/8/R3K3 w - - 99 80");
+        assert!(!b.is_fifty_move_draw());
+        assert!(!b.is_seventy_five_move_draw());
+        let b = board("4k3/8/8/8/8/8/8/R3K3 w - - 100 80");
+        assert!(b.is_fifty_move_draw());
+        assert!(!b.is_seventy_five_move_draw());
+        let b = board("4k3/8/8/8/8/8/8/R3K3 w - - 149 80");
+        assert!(!b.is_seventy_five_move_draw());
+        let b = board("4k3/8/8/8/8/8/8/R3K3 w - - 150 80");
+        assert!(b.is_fifty_move_draw());
+        assert!(b.is_seventy_five_move_draw());
+
+        // La jugada 100 sin capturas ni peones llega a la regla; un avance de peón reinicia la cuenta
+        let mut b = board("4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80");
+        play(&mut b, "a1a2");
+        assert!(b.is_fifty_move_draw());
+        let mut b = board("4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80");
+        play(&mut b, "e2e4");
+        assert_eq!(b.halfmove_clock, 0);
+        assert!(!b.is_fifty_move_draw());
+    }
+
+    #[test]
+    fn threefold_repetition() {
+        let mut b = Board::new();
+        let shuffle = "g1f3 g8f6 f3g1 f6g8";
+        let history = play(&mut b, shuffle);
+        assert_eq!(b.repetition_count(&history), 2);
+
+        let mut b = Board::new();
+        let history = play(&mut b, &format!("{} {}", shuffle, shuffle));
+        assert_eq!(b.repetition_count(&history), 3);
+
+        // Una captura o un avance de peón rompe la repetición
+        let mut b = Board::new();
+        let history = play(&mut b, &format!("{} e2e4 e7e5 {}", shuffle, shuffle));
+        assert_eq!(b.repetition_count(&history), 2);
     }
 }
@@ -0,0 +1,248 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use rand::seq::SliceRandom;
+use crate::board::Board;
+use crate::book_keys::RANDOM64;
+use crate::moves::Move;
+use crate::pieces::{PieceColor, PieceType};
+
+// Jugadas de cada bando en las que se consulta el libro si no se indica otra cosa
+pub const DEFAULT_DEPTH: u32 = 12;
+// Cada entrada de un libro Polyglot ocupa 16 bytes: clave, jugada, peso y aprendizaje (sin usar)
+const ENTRY_SIZE: usize = 16;
+
+// Cómo se elige entre las jugadas del libro para una posición
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BookMode {
+    #[default]
+    Weighted, // Al azar, con la probabilidad proporcional al peso de cada jugada
+    BestMove, // Siempre la de más peso
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    key: u64,
+    raw_move: u16,
+    weight: u16,
+}
+
+// Libro de aperturas en formato Polyglot (.bin)
+pub struct OpeningBook {
+    entries: Vec<Entry>, // Ordenadas por clave, como en el archivo
+    pub mode: BookMode,
+    pub depth: u32, // Pasadas estas jugadas de cada bando la IA ya no consulta el libro
+}
+
+impl OpeningBook {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "el libro no tiene formato Polyglot"));
+        }
+        let mut entries: Vec<Entry> = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|chunk| Entry {
+                key: u64::from_be_bytes(chunk[0..8].try_into().unwrap()),
+                raw_move: u16::from_be_bytes([chunk[8], chunk[9]]),
+                weight: u16::from_be_bytes([chunk[10], chunk[11]]),
+            })
+            .collect();
+        // Los libros ya vienen ordenados; la búsqueda binaria no debe depender de ello
+        entries.sort_by_key(|entry| entry.key);
+        Ok(Self { entries, mode: BookMode::default(), depth: DEFAULT_DEPTH })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Jugadas legales del libro para la posición, con su peso (las ilegales se descartan)
+    pub fn moves(&self, board: &mut Board) -> Vec<(Move, u16)> {
+        let key = polyglot_key(board);
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let legal = board.generate_legal_moves();
+        self.entries[start..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .filter_map(|entry| {
+                let mv = legal.iter().find(|&&mv| encode_move(mv) == entry.raw_move)?;
+                Some((*mv, entry.weight))
+            })
+            .collect()
+    }
+
+    // Jugada del libro según `mode`, o None si la posición no está o ya se pasó de `depth`.
+    // `ply` son los medios movimientos jugados desde el principio de la partida.
+    pub fn probe(&self, board: &mut Board, ply: usize) -> Option<Move> {
+        if ply >= 2 * self.depth as usize {
+            return None;
+        }
+        let moves = self.moves(board);
+        match self.mode {
+            // max_by_key devuelve la última de las empatadas; se prefiere la primera del libro
+            BookMode::BestMove => moves.iter().rev().max_by_key(|&&(_, weight)| weight).map(|&(mv, _)| mv),
+            BookMode::Weighted => {
+                let mut rng = rand::thread_rng();
+                moves.choose_weighted(&mut rng, |&(_, weight)| weight).ok().map(|&(mv, _)| mv)
+            }
+        }
+    }
+}
+
+// Clave Polyglot de la posición. No coincide con `Board::hash`: usa los números del formato y
+// solo cuenta la captura al paso si algún peón del bando que mueve puede hacerla.
+pub fn polyglot_key(board: &Board) -> u64 {
+    let mut key = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            if let Some(piece) = board.piece_at((x, y)) {
+                let kind = match piece.piece_type {
+                    PieceType::Pawn => 0,
+                    PieceType::Knight => 1,
+                    PieceType::Bishop => 2,
+                    PieceType::Rook => 3,
+                    PieceType::Queen => 4,
+                    PieceType::King => 5,
+                } * 2 + (piece.color == PieceColor::White) as usize;
+                key ^= RANDOM64[64 * kind + 8 * (7 - y) + x];
+            }
+        }
+    }
+
+    let castling = [
+        board.castling.white_kingside,
+        board.castling.white_queenside,
+        board.castling.black_kingside,
+        board.castling.black_queenside,
+    ];
+    for (i, _) in castling.iter().enumerate().filter(|&(_, &allowed)| allowed) {
+        key ^= RANDOM64[768 + i];
+    }
+
+    if let Some((x, y)) = board.en_passant {
+        // El peón que captura estaría junto al que avanzó dos casillas
+        let color = board.side_to_move;
+        let pawn_y = if color == PieceColor::White { y + 1 } else { y - 1 };
+        let can_capture = [x.wrapping_sub(1), x + 1].into_iter().any(|file| {
+            file < 8 && board.piece_at((file, pawn_y)).is_some_and(|p| p.piece_type == PieceType::Pawn && p.color == color)
+        });
+        if can_capture {
+            key ^= RANDOM64[772 + x];
+        }
+    }
+
+    if board.side_to_move == PieceColor::White {
+        key ^= RANDOM64[780];
+    }
+    key
+}
+
+// Jugada en la codificación de Polyglot: destino en los bits 0-5, origen en los 6-11 (fila * 8 +
+// columna, con la fila 0 la primera) y coronación en los 12-14. El enroque se escribe como el
+// rey capturando su propia torre ("e1h1").
+fn encode_move(mv: Move) -> u16 {
+    let square = |(x, y): (usize, usize)| ((7 - y) * 8 + x) as u16;
+    let to = if mv.is_castle {
+        (if mv.to.0 > mv.from.0 { 7 } else { 0 }, mv.to.1)
+    } else {
+        mv.to
+    };
+    let promotion = match mv.promotion {
+        Some(PieceType::Knight) => 1,
+        Some(PieceType::Bishop) => 2,
+        Some(PieceType::Rook) => 3,
+        Some(PieceType::Queen) => 4,
+        _ => 0,
+    };
+    square(to) | (square(mv.from) << 6) | (promotion << 12)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Libro pequeño que acompaña al juego (aperturas principales de 1.e4, 1.d4, 1.c4 y 1.Cf3)
+    const BUNDLED_BOOK: &[u8] = include_bytes!("../assets/book.bin");
+
+    fn play(moves: &str) -> Board {
+        let mut board = Board::new();
+        for text in moves.split_whitespace() {
+            let mv = board.generate_legal_moves().into_iter().find(|mv| mv.to_string() == text).unwrap();
+            board.make_move(mv);
+        }
+        board
+    }
+
+    // Claves de ejemplo de la especificación del formato Polyglot
+    #[test]
+    fn polyglot_keys() {
+        let cases = [
+            ("", 0x463B_9618_1691_FC9C),
+            ("e2e4", 0x823C_9B50_FD11_4196),
+            ("e2e4 d7d5", 0x0756_B944_61C5_0FB0),
+            ("e2e4 d7d5 e4e5", 0x662F_AFB9_65DB_29D4),
+            ("e2e4 d7d5 e4e5 f7f5", 0x22A4_8B5A_8E47_FF78),
+            ("e2e4 d7d5 e4e5 f7f5 e1e2", 0x652A_607C_A3F2_42C1),
+            ("e2e4 d7d5 e4e5 f7f5 e1e2 e8f7", 0x00FD_D303_C946_BDD9),
+            ("a2a4 b7b5 h2h4 b5b4 c2c4", 0x3C81_23EA_7B06_7637),
+            ("a2a4 b7b5 h2h4 b5b4 c2c4 b4c3 a1a3", 0x5C3F_9B82_9B27_9560),
+        ];
+        for (moves, key) in cases {
+            assert_eq!(polyglot_key(&play(moves)), key, "clave tras '{}'", moves);
+        }
+    }
+
+    #[test]
+    fn bundled_book_moves() {
+        let book = OpeningBook::from_bytes(BUNDLED_BOOK).unwrap();
+        let mut board = Board::new();
+        let mut moves: Vec<(String, u16)> = book.moves(&mut board).into_iter().map(|(mv, w)| (mv.to_string(), w)).collect();
+        moves.sort();
+        let expected = [("c2c4", 15), ("d2d4", 90), ("e2e4", 135), ("g1f3", 10)];
+        assert_eq!(moves, expected.map(|(mv, w)| (mv.to_string(), w)));
+
+        // El enroque se guarda como "e1h1" y se convierte en la jugada de enroque del tablero
+        let mut board = play("e2e4 e7e5 g1f3 b8c6 f1b5 a7a6 b5a4 g8f6");
+        let castle = book.probe(&mut board, 8).unwrap();
+        assert!(castle.is_castle);
+        assert_eq!(castle.to_string(), "e1g1");
+    }
+
+    #[test]
+    fn selection_modes() {
+        let mut book = OpeningBook::from_bytes(BUNDLED_BOOK).unwrap();
+        let mut board = Board::new();
+
+        book.mode = BookMode::BestMove;
+        for _ in 0..10 {
+            assert_eq!(book.probe(&mut board, 0).unwrap().to_string(), "e2e4");
+        }
+
+        book.mode = BookMode::Weighted;
+        let picks: Vec<String> = (0..200).map(|_| book.probe(&mut board, 0).unwrap().to_string()).collect();
+        assert!(picks.iter().all(|mv| ["e2e4", "d2d4", "c2c4", "g1f3"].contains(&mv.as_str())));
+        assert!(picks.iter().any(|mv| mv == "e2e4") && picks.iter().any(|mv| mv == "d2d4"));
+    }
+
+    #[test]
+    fn depth_and_unknown_positions() {
+        let mut book = OpeningBook::from_bytes(BUNDLED_BOOK).unwrap();
+        book.depth = 1;
+        let mut board = play("e2e4");
+        assert!(book.probe(&mut board, 1).is_some());
+        assert!(book.probe(&mut board, 2).is_none());
+
+        let mut board = play("a2a3");
+        assert!(book.probe(&mut board, 1).is_none());
+
+        assert!(OpeningBook::from_bytes(&BUNDLED_BOOK[..20]).is_err());
+    }
+}
@@ -0,0 +1,523 @@
+use crate::bitboard::{self, Bitboard, bit, position, squares};
+use crate::board::Board;
+use crate::pieces::{PieceColor, PieceType};
+
+// Evaluación en centipeones, interpolada entre medio juego y final según el material que queda.
+// Las tablas de casillas están escritas desde el lado de las Blancas con a8 en el índice 0,
+// igual que las casillas del tablero; para las Negras se refleja la fila (índice ^ 56).
+
+const PIECE_TYPES: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+// Fase de la partida: 24 con todas las piezas en el tablero, 0 con solo Reyes y peones
+const MAX_PHASE: i32 = 24;
+
+const BISHOP_PAIR: (i32, i32) = (30, 50);
+const DOUBLED_PAWN: (i32, i32) = (-10, -20);
+const ISOLATED_PAWN: (i32, i32) = (-10, -15);
+// Peón pasado según su fila contando desde la propia (la 2ª es el índice 1)
+const PASSED_PAWN_MG: [i32; 8] = [0, 5, 10, 15, 25, 45, 70, 0];
+const PASSED_PAWN_EG: [i32; 8] = [0, 10, 20, 35, 60, 95, 140, 0];
+// Escudo de peones del Rey enrocado: penalización por columna sin peón delante
+const SHIELD_PAWN_ADVANCED: i32 = -10;
+const SHIELD_PAWN_MISSING: i32 = -25;
+// Ataques a las casillas del Rey: peso por pieza atacante y por casilla atacada, escalado
+// según cuántas piezas atacan a la vez (una sola pieza apenas es peligrosa)
+const KING_ATTACK_SCALE: [i32; 8] = [0, 0, 50, 75, 88, 94, 97, 99];
+
+const PAWN_MG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     98, 134,  61,  95,  68, 126,  34, -11,
+     -6,   7,  26,  31,  65,  56,  25, -20,
+    -14,  13,   6,  21,  23,  12,  17, -23,
+    -27,  -2,  -5,  12,  17,   6,  10, -25,
+    -26,  -4,  -4, -10,   3,   3,  33, -12,
+    -35,  -1, -20, -23, -15,  24,  38, -22,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+const PAWN_EG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+    178, 173, 158, 134, 147, 132, 165, 187,
+     94, 100,  85,  67,  56,  53,  82,  84,
+     32,  24,  13,   5,  -2,   4,  17,  17,
+     13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+      4,   7,  -6,   1,   0,  -5,  -1,  -8,
+     13,   8,   8,  10,  13,   0,   2,  -7,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+const KNIGHT_MG: [i32; 64] = [
+   -167, -89, -34, -49,  61, -97, -15,-107,
+    -73, -41,  72,  36,  23,  62,   7, -17,
+    -47,  60,  37,  65,  84, 129,  73,  44,
+     -9,  17,  19,  53,  37,  69,  18,  22,
+    -13,   4,  16,  13,  28,  19,  21,  -8,
+    -23,  -9,  12,  10,  19,  17,  25, -16,
+    -29, -53, -12,  -3,  -1,  18, -14, -19,
+   -105, -21, -58, -33, -17, -28, -19, -23,
+];
+
+const KNIGHT_EG: [i32; 64] = [
+    -58, -38, -13, -28, -31, -27, -63, -99,
+    -25,  -8, -25,  -2,  -9, -25, -24, -52,
+    -24, -20,  10,   9,  -1,  -9, -19, -41,
+    -17,   3,  22,  22,  22,  11,   8, -18,
+    -18,  -6,  16,  25,  16,  17,   4, -18,
+    -23,  -3,  -1,  15,  10,  -3, -20, -22,
+    -42, -20, -10,  -5,  -2, -20, -23, -44,
+    -29, -51, -23, -15, -22, -18, -50, -64,
+];
+
+const BISHOP_MG: [i32; 64] = [
+    -29,   4, -82, -37, -25, -42,   7,  -8,
+    -26,  16, -18, -13,  30,  59,  18, -47,
+    -16,  37,  43,  40,  35,  50,  37,  -2,
+     -4,   5,  19,  50,  37,  37,   7,  -2,
+     -6,  13,  13,  26,  34,  12,  10,   4,
+      0,  15,  15,  15,  14,  27,  18,  10,
+      4,  15,  16,   0,   7,  21,  33,   1,
+    -33,  -3, -14, -21, -13, -12, -39, -21,
+];
+
+const BISHOP_EG: [i32; 64] = [
+    -14, -21, -11,  -8,  -7,  -9, -17, -24,
+     -8,  -4,   7, -12,  -3, -13,  -4, -14,
+      2,  -8,   0,  -1,  -2,   6,   0,   4,
+     -3,   9,  12,   9,  14,  10,   3,   2,
+     -6,   3,  13,  19,   7,  10,  -3,  -9,
+    -12,  -3,   8,  10,  13,   3,  -7, -15,
+    -14, -18,  -7,  -1,   4,  -9, -15, -27,
+    -23,  -9, -23,  -5,  -9, -16,  -5, -17,
+];
+
+const ROOK_MG: [i32; 64] = [
+     32,  42,  32,  51,  63,   9,  31,  43,
+     27,  32,  58,  62,  80,  67,  26,  44,
+     -5,  19,  26,  36,  17,  45,  61,  16,
+    -24, -11,   7,  26,  24,  35,  -8, -20,
+    -36, -26, -12,  -1,   9,  -7,   6, -23,
+    -45, -25, -16, -17,   3,   0,  -5, -33,
+    -44, -16, -20,  -9,  -1,  11,  -6, -71,
+    -19, -13,   1,  17,  16,   7, -37, -26,
+];
+
+const ROOK_EG: [i32; 64] = [
+     13,  10,  18,  15,  12,  12,   8,   5,
+     11,  13,  13,  11,  -3,   3,   8,   3,
+      7,   7,   7,   5,   4,  -3,  -5,  -3,
+      4,   3,  13,   1,   2,   1,  -1,   2,
+      3,   5,   8,   4,  -5,  -6,  -8, -11,
+     -4,   0,  -5,  -1,  -7, -12,  -8, -16,
+     -6,  -6,   0,   2,  -9,  -9, -11,  -3,
+     -9,   2,   3,  -1,  -5, -13,   4, -20,
+];
+
+const QUEEN_MG: [i32; 64] = [
+    -28,   0,  29,  12,  59,  44,  43,  45,
+    -24, -39,  -5,   1, -16,  57,  28,  54,
+    -13, -17,   7,   8,  29,  56,  47,  57,
+    -27, -27, -16, -16,  -1,  17,  -2,   1,
+     -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+    -14,   2, -11,  -2,  -5,   2,  14,   5,
+    -35,  -8,  11,   2,   8,  15,  -3,   1,
+     -1, -18,  -9,  10, -15, -25, -31, -50,
+];
+
+const QUEEN_EG: [i32; 64] = [
+     -9,  22,  22,  27,  27,  19,  10,  20,
+    -17,  20,  32,  41,  58,  25,  30,   0,
+    -20,   6,   9,  49,  47,  35,  19,   9,
+      3,  22,  24,  45,  57,  40,  57,  36,
+    -18,  28,  19,  47,  31,  34,  39,  23,
+    -16, -27,  15,   6,   9,  17,  10,   5,
+    -22, -23, -30, -16, -16, -23, -36, -32,
+    -33, -28, -22, -43,  -5, -32, -20, -41,
+];
+
+// En el medio juego el Rey quiere estar enrocado; en el final, en el centro
+const KING_MG: [i32; 64] = [
+    -65,  23,  16, -15, -56, -34,   2,  13,
+     29,  -1, -20,  -7,  -8,  -4, -38, -29,
+     -9,  24,   2, -16, -20,   6,  22, -22,
+    -17, -20, -12, -27, -30, -25, -14, -36,
+    -49,  -1, -27, -39, -46, -44, -33, -51,
+    -14, -14, -22, -46, -44, -30, -15, -27,
+      1,   7,  -8, -64, -43, -16,   9,   8,
+    -15,  36,  12, -54,   8, -28,  24,  14,
+];
+
+const KING_EG: [i32; 64] = [
+    -74, -35, -18, -18, -11,  15,   4, -17,
+    -12,  17,  14,  17,  17,  38,  23,  11,
+     10,  17,  23,  15,  20,  45,  44,  13,
+     -8,  22,  24,  27,  26,  33,  26,   3,
+    -18,  -4,  21,  24,  27,  23,   9, -11,
+    -19,  -3,  11,  21,  23,  16,   7,  -9,
+    -27, -11,   4,  13,  14,   4,  -5, -17,
+    -53, -34, -21, -11, -28, -14, -24, -43,
+];
+
+// Valor (medio juego, final), tablas de casillas y peso en la fase de cada tipo de pieza
+struct PieceTerms {
+    value: (i32, i32),
+    table_mg: &'static [i32; 64],
+    table_eg: &'static [i32; 64],
+    phase: i32,
+}
+
+fn piece_terms(piece_type: PieceType) -> PieceTerms {
+    match piece_type {
+        PieceType::Pawn => PieceTerms { value: (82, 94), table_mg: &PAWN_MG, table_eg: &PAWN_EG, phase: 0 },
+        PieceType::Knight => PieceTerms { value: (337, 281), table_mg: &KNIGHT_MG, table_eg: &KNIGHT_EG, phase: 1 },
+        PieceType::Bishop => PieceTerms { value: (365, 297), table_mg: &BISHOP_MG, table_eg: &BISHOP_EG, phase: 1 },
+        PieceType::Rook => PieceTerms { value: (477, 512), table_mg: &ROOK_MG, table_eg: &ROOK_EG, phase: 2 },
+        PieceType::Queen => PieceTerms { value: (1025, 936), table_mg: &QUEEN_MG, table_eg: &QUEEN_EG, phase: 4 },
+        PieceType::King => PieceTerms { value: (0, 0), table_mg: &KING_MG, table_eg: &KING_EG, phase: 0 },
+    }
+}
+
+// Movilidad de las piezas que la tienen en cuenta y su peso en los ataques al Rey rival
+struct MobilityTerms {
+    weight: (i32, i32), // Por casilla disponible (medio juego, final)
+    average: i32,       // Casillas habituales: con menos se penaliza, con más se premia
+    king_attack: i32,   // Por cada casilla atacada junto al Rey rival
+}
+
+fn mobility_terms(piece_type: PieceType) -> Option<MobilityTerms> {
+    let (weight, average, king_attack) = match piece_type {
+        PieceType::Knight => ((4, 4), 4, 20),
+        PieceType::Bishop => ((5, 5), 7, 20),
+        PieceType::Rook => ((2, 4), 7, 40),
+        PieceType::Queen => ((1, 2), 14, 80),
+        PieceType::Pawn | PieceType::King => return None,
+    };
+    Some(MobilityTerms { weight, average, king_attack })
+}
+
+fn attacks(piece_type: PieceType, square: usize, occupied: Bitboard) -> Bitboard {
+    match piece_type {
+        PieceType::Knight => bitboard::knight_attacks(square),
+        PieceType::Bishop => bitboard::bishop_attacks(square, occupied),
+        PieceType::Rook => bitboard::rook_attacks(square, occupied),
+        PieceType::Queen => bitboard::rook_attacks(square, occupied) | bitboard::bishop_attacks(square, occupied),
+        PieceType::King => bitboard::king_attacks(square),
+        PieceType::Pawn => 0,
+    }
+}
+
+// Puntuación desde el punto de vista de `color`
+pub fn evaluate(board: &Board, color: PieceColor) -> i32 {
+    // Sin material para dar mate el resultado ya es tablas
+    if board.is_insufficient_material() {
+        return 0;
+    }
+
+    let white = side_terms(board, PieceColor::White);
+    let black = side_terms(board, PieceColor::Black);
+    let mg = white.mg - black.mg;
+    let eg = white.eg - black.eg;
+
+    let phase = (white.phase + black.phase).min(MAX_PHASE);
+    let score = (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE;
+    match color {
+        PieceColor::White => score,
+        PieceColor::Black => -score,
+    }
+}
+
+struct SideTerms {
+    mg: i32,
+    eg: i32,
+    phase: i32,
+}
+
+// Todos los términos de un bando. La seguridad de su Rey resta según cómo le atacan las piezas rivales.
+fn side_terms(board: &Board, color: PieceColor) -> SideTerms {
+    let enemy = color.opposite();
+    let occupied = board.occupied();
+    let own = board.pieces_of(color);
+    let pawns = board.pieces(color, PieceType::Pawn);
+    let enemy_pawns = board.pieces(enemy, PieceType::Pawn);
+    let mut terms = SideTerms { mg: 0, eg: 0, phase: 0 };
+
+    // Material y casillas
+    for piece_type in PIECE_TYPES {
+        let piece = piece_terms(piece_type);
+        for sq in squares(board.pieces(color, piece_type)) {
+            let index = relative_square(color, sq);
+            terms.mg += piece.value.0 + piece.table_mg[index];
+            terms.eg += piece.value.1 + piece.table_eg[index];
+            terms.phase += piece.phase;
+        }
+    }
+
+    // Movilidad: casillas libres o con piezas rivales que no controla un peón enemigo
+    let enemy_pawn_attacks = squares(enemy_pawns)
+        .fold(0, |acc, sq| acc | bitboard::pawn_attacks(enemy, sq));
+    let available = !own & !enemy_pawn_attacks;
+    for piece_type in PIECE_TYPES {
+        let Some(mobility) = mobility_terms(piece_type) else {
+            continue;
+        };
+        for sq in squares(board.pieces(color, piece_type)) {
+            let moves = (attacks(piece_type, sq, occupied) & available).count_ones() as i32 - mobility.average;
+            terms.mg += moves * mobility.weight.0;
+            terms.eg += moves * mobility.weight.1;
+        }
+    }
+
+    if board.pieces(color, PieceType::Bishop).count_ones() >= 2 {
+        terms.mg += BISHOP_PAIR.0;
+        terms.eg += BISHOP_PAIR.1;
+    }
+
+    let (mg, eg) = pawn_structure(color, pawns, enemy_pawns);
+    terms.mg += mg;
+    terms.eg += eg;
+
+    terms.mg += king_safety(board, color);
+    terms
+}
+
+// Peones doblados, aislados y pasados de `color` (medio juego, final)
+fn pawn_structure(color: PieceColor, pawns: Bitboard, enemy_pawns: Bitboard) -> (i32, i32) {
+    let (mut mg, mut eg) = (0, 0);
+    for file in 0..8 {
+        let count = (pawns & bitboard::file_mask(file)).count_ones() as i32;
+        if count > 1 {
+            mg += DOUBLED_PAWN.0 * (count - 1);
+            eg += DOUBLED_PAWN.1 * (count - 1);
+        }
+    }
+    for sq in squares(pawns) {
+        let (x, _) = position(sq);
+        if pawns & bitboard::adjacent_files(x) == 0 {
+            mg += ISOLATED_PAWN.0;
+            eg += ISOLATED_PAWN.1;
+        }
+        // Pasado: sin peones rivales por delante ni en las columnas vecinas, y sin uno propio
+        // delante en la misma columna (de un peón doblado solo cuenta el de delante)
+        let ahead = bitboard::passed_pawn_mask(color, sq);
+        if enemy_pawns & ahead == 0 && pawns & ahead & bitboard::file_mask(x) == 0 {
+            let rank = relative_rank(color, sq);
+            mg += PASSED_PAWN_MG[rank];
+            eg += PASSED_PAWN_EG[rank];
+        }
+    }
+    (mg, eg)
+}
+
+// Seguridad del Rey (solo cuenta en el medio juego): peones delante del Rey enrocado
+// y piezas rivales que atacan las casillas que lo rodean
+fn king_safety(board: &Board, color: PieceColor) -> i32 {
+    let king = board.pieces(color, PieceType::King);
+    if king == 0 {
+        return 0;
+    }
+    let king_sq = king.trailing_zeros() as usize;
+    let (kx, _) = position(king_sq);
+    let pawns = board.pieces(color, PieceType::Pawn);
+    let mut score = 0;
+
+    // Escudo de peones, si el Rey está enrocado (columnas a-c o g-h) en sus dos primeras filas.
+    // En el centro no tiene delante peones que mantener y el escudo lo penalizaría sin motivo.
+    if relative_rank(color, king_sq) <= 1 && (kx <= 2 || kx >= 6) {
+        let forward: isize = if color == PieceColor::White { -8 } else { 8 };
+        for file in kx.saturating_sub(1)..=(kx + 1).min(7) {
+            let base = (king_sq - kx + file) as isize;
+            let one = base + forward;
+            let two = base + 2 * forward;
+            if (0..64).contains(&one) && pawns & bit(one as usize) != 0 {
+                continue;
+            }
+            score += if (0..64).contains(&two) && pawns & bit(two as usize) != 0 {
+                SHIELD_PAWN_ADVANCED
+            } else {
+                SHIELD_PAWN_MISSING
+            };
+        }
+    }
+
+    // Piezas rivales que atacan la zona del Rey
+    let enemy = color.opposite();
+    let zone = bitboard::king_attacks(king_sq) | king;
+    let occupied = board.occupied();
+    let (mut attackers, mut attack_value) = (0, 0);
+    for piece_type in PIECE_TYPES {
+        let Some(mobility) = mobility_terms(piece_type) else {
+            continue;
+        };
+        for sq in squares(board.pieces(enemy, piece_type)) {
+            let hits = (attacks(piece_type, sq, occupied) & zone).count_ones() as i32;
+            if hits > 0 {
+                attackers += 1;
+                attack_value += mobility.king_attack * hits;
+            }
+        }
+    }
+    score -= attack_value * KING_ATTACK_SCALE[attackers.min(7)] / 100;
+
+    score
+}
+
+// Casilla vista desde las Blancas: para las Negras se refleja la fila
+fn relative_square(color: PieceColor, square: usize) -> usize {
+    match color {
+        PieceColor::White => square,
+        PieceColor::Black => square ^ 56,
+    }
+}
+
+// Fila contando desde la del propio bando (0 = primera fila)
+fn relative_rank(color: PieceColor, square: usize) -> usize {
+    7 - relative_square(color, square) / 8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    // La misma posición con los colores cambiados: filas al revés, mayúsculas por minúsculas
+    // y el turno, los enroques y el al paso del otro bando
+    fn mirror(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |s: &str| -> String {
+            s.chars()
+                .map(|c| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() })
+                .collect()
+        };
+        let placement: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+        let side = if fields[1] == "w" { "b" } else { "w" };
+        let castling = if fields[2] == "-" {
+            "-".to_string()
+        } else {
+            let swapped = swap_case(fields[2]);
+            "KQkq".chars().filter(|c| swapped.contains(*c)).collect()
+        };
+        let en_passant = match fields[3] {
+            "-" => "-".to_string(),
+            sq => format!("{}{}", &sq[..1], if &sq[1..] == "3" { 6 } else { 3 }),
+        };
+        format!("{} {} {} {} {} {}", placement.join("/"), side, castling, en_passant, fields[4], fields[5])
+    }
+
+    #[test]
+    fn colour_mirror_is_symmetric() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            // Kiwipete
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r2q1rk1/pp2ppbp/2np1np1/8/3NP3/2N1BP2/PPPQ2PP/2KR1B1R w - - 4 10",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "6k1/5ppp/8/3P4/8/8/5PPP/6K1 b - - 0 30",
+        ];
+        for fen in fens {
+            let mirrored = mirror(fen);
+            let score = evaluate(&board(fen), PieceColor::White);
+            assert_eq!(score, -evaluate(&board(&mirrored), PieceColor::White), "{}", fen);
+            assert_eq!(score, evaluate(&board(&mirrored), PieceColor::Black), "{}", fen);
+        }
+    }
+
+    // Términos de estructura de peones de las Blancas en la posición
+    fn white_pawns(fen: &str) -> (i32, i32) {
+        let b = board(fen);
+        pawn_structure(
+            PieceColor::White,
+            b.pieces(PieceColor::White, PieceType::Pawn),
+            b.pieces(PieceColor::Black, PieceType::Pawn),
+        )
+    }
+
+    #[test]
+    fn pawn_structure_terms() {
+        // Dos peones juntos en su fila: ni doblados ni aislados, los dos pasados
+        assert_eq!(white_pawns("4k3/8/8/8/8/8/PP6/4K3 w - - 0 1"), (2 * PASSED_PAWN_MG[1], 2 * PASSED_PAWN_EG[1]));
+        // Doblados y aislados, y no pasados porque el peón de d5 controla la columna vecina
+        assert_eq!(
+            white_pawns("4k3/8/8/3p4/8/2P5/2P5/4K3 w - - 0 1"),
+            (DOUBLED_PAWN.0 + 2 * ISOLATED_PAWN.0, DOUBLED_PAWN.1 + 2 * ISOLATED_PAWN.1),
+        );
+        // Peón aislado y pasado en la sexta; el peón rival de a7 no está en columnas vecinas
+        assert_eq!(
+            white_pawns("4k3/p7/4P3/8/8/8/8/4K3 w - - 0 1"),
+            (ISOLATED_PAWN.0 + PASSED_PAWN_MG[5], ISOLATED_PAWN.1 + PASSED_PAWN_EG[5]),
+        );
+        // De los doblados solo el de delante es pasado
+        assert_eq!(
+            white_pawns("4k3/8/8/8/8/P7/PP6/4K3 w - - 0 1"),
+            (DOUBLED_PAWN.0 + PASSED_PAWN_MG[2] + PASSED_PAWN_MG[1], DOUBLED_PAWN.1 + PASSED_PAWN_EG[2] + PASSED_PAWN_EG[1]),
+        );
+        // Las Negras cuentan las filas desde su lado
+        let b = board("4k3/8/8/8/3p4/8/8/4K3 b - - 0 1");
+        assert_eq!(
+            pawn_structure(PieceColor::Black, b.pieces(PieceColor::Black, PieceType::Pawn), 0),
+            (ISOLATED_PAWN.0 + PASSED_PAWN_MG[4], ISOLATED_PAWN.1 + PASSED_PAWN_EG[4]),
+        );
+    }
+
+    #[test]
+    fn bishop_pair() {
+        // Los dos Alfiles no se estorban: lo que valen juntos menos lo que vale cada uno es la pareja
+        let terms = |fen: &str| {
+            let t = side_terms(&board(fen), PieceColor::White);
+            (t.mg, t.eg)
+        };
+        let both = terms("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1");
+        let c1 = terms("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1");
+        let f1 = terms("4k3/8/8/8/8/8/8/4KB2 w - - 0 1");
+        let none = terms("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!((both.0 - c1.0 - f1.0 + none.0, both.1 - c1.1 - f1.1 + none.1), BISHOP_PAIR);
+    }
+
+    #[test]
+    fn phase_taper() {
+        // Devuelve la evaluación y sus términos de medio juego y final, y la fase sin limitar
+        let terms = |fen: &str| {
+            let b = board(fen);
+            let white = side_terms(&b, PieceColor::White);
+            let black = side_terms(&b, PieceColor::Black);
+            (evaluate(&b, PieceColor::White), white.mg - black.mg, white.eg - black.eg, white.phase + black.phase)
+        };
+        // Con todas las piezas solo cuenta el medio juego y con solo peones, el final
+        let (score, mg, _, phase) = terms("rnbqkbnr/1ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!((score, phase), (mg, MAX_PHASE));
+        let (score, _, eg, phase) = terms("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        assert_eq!((score, phase), (eg, 0));
+        // Sin Damas ni Torres queda un tercio de la fase
+        let (score, mg, eg, phase) = terms("1nb1kbn1/pppppppp/8/8/8/8/PPPPPPPP/1NB1KBN1 w - - 0 1");
+        assert_eq!(phase, 8);
+        assert_eq!(score, (mg * 8 + eg * 16) / MAX_PHASE);
+        // Las promociones no suben la fase por encima del máximo
+        let (score, mg, _, phase) = terms("qqq1k3/8/8/8/8/8/8/QQQQK3 w - - 0 1");
+        assert!(phase > MAX_PHASE);
+        assert_eq!(score, mg);
+    }
+
+    #[test]
+    fn king_shield_only_for_castled_king() {
+        let white = |fen: &str| king_safety(&board(fen), PieceColor::White);
+        // En el centro no hay escudo que pedir
+        assert_eq!(white("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), 0);
+        assert_eq!(white("4k3/8/8/8/8/8/8/3K4 w - - 0 1"), 0);
+        // Enrocado corto y largo sin peones delante
+        assert_eq!(white("4k3/8/8/8/8/8/8/6K1 w - - 0 1"), 3 * SHIELD_PAWN_MISSING);
+        assert_eq!(white("4k3/8/8/8/8/8/8/1K6 w - - 0 1"), 3 * SHIELD_PAWN_MISSING);
+        assert_eq!(white("4k3/8/8/8/8/8/5PPP/6K1 w - - 0 1"), 0);
+        assert_eq!(white("4k3/8/8/8/8/6P1/5P1P/6K1 w - - 0 1"), SHIELD_PAWN_ADVANCED);
+        // Las Negras miran hacia abajo
+        let black = king_safety(&board("6k1/5p1p/6p1/8/8/8/8/4K3 b - - 0 1"), PieceColor::Black);
+        assert_eq!(black, SHIELD_PAWN_ADVANCED);
+    }
+}
@@ -1,27 +1,45 @@
-use ggez::{Context, GameResult, graphics};
+use ggez::{Context, graphics};
 use ggez::audio::SoundSource;
 use ggez::event::{self, MouseButton};
 use ggez::graphics::{Mesh, DrawParam, Color, DrawMode, Rect, Text};
 use ggez::input::keyboard::KeyCode;
 use rand::Rng;
 use std::net::{TcpListener, TcpStream};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::thread;
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 use local_ip_address::local_ip;
 
 use crate::constants::*;
 use crate::resources::Resources;
 use crate::pieces::{PieceColor};
+use crate::moves::Move;
 use crate::board::{Board, BOARD_SIZE};
-use crate::ai;
+use crate::result::{EndReason, GameResult};
+use crate::san;
+use crate::pgn::{self, GameRecord};
+use crate::ai::{self, Difficulty};
+use crate::transposition::TranspositionTable;
+use crate::book::OpeningBook;
+use crate::syzygy::Tablebases;
 use crate::network::{NetworkClient, NetworkMessage};
 
+// Posición vertical del selector de color y de la lista de niveles en las pantallas de preparación
+const AI_COLOR_ROW_Y: f32 = 200.0;
+const AI_LEVELS_Y: f32 = 320.0;
+const HOST_COLOR_ROW_Y: f32 = 480.0;
+
 #[derive(PartialEq, Clone, Copy)]
 enum AppMode {
     Menu,
+    AiSetup, // Eligiendo el nivel de la IA
     HostWait,
     JoinInput,
     Playing,
+    LoadInput, // Escribiendo la ruta de un archivo PGN
+    Replay,    // Repasando una partida cargada
 }
 
 #[derive(PartialEq, Clone, Copy)]
@@ -30,58 +48,196 @@ enum GameType {
     Multiplayer,
 }
 
+// Color que elige el jugador contra la IA o el anfitrión de una partida en red
+#[derive(PartialEq, Clone, Copy)]
+enum ColorChoice {
+    White,
+    Black,
+    Random,
+}
+
+impl ColorChoice {
+    const ALL: [ColorChoice; 3] = [ColorChoice::White, ColorChoice::Black, ColorChoice::Random];
+
+    fn label(self) -> &'static str {
+        match self {
+            ColorChoice::White => "Blancas",
+            ColorChoice::Black => "Negras",
+            ColorChoice::Random => "Al azar",
+        }
+    }
+
+    // Color con el que se juega; "al azar" se sortea en cada partida
+    fn resolve(self) -> PieceColor {
+        match self {
+            ColorChoice::White => PieceColor::White,
+            ColorChoice::Black => PieceColor::Black,
+            ColorChoice::Random => {
+                let mut rng = rand::thread_rng();
+                if rng.gen_bool(0.5) { PieceColor::White } else { PieceColor::Black }
+            }
+        }
+    }
+
+    // Botón de cada opción en las pantallas de preparación, en la fila `y`
+    fn rect(i: usize, y: f32) -> Rect {
+        Rect::new(300.0 + 130.0 * i as f32, y, 110.0, 30.0)
+    }
+}
+
+// Opciones recibidas por línea de comandos
+pub struct GameOptions {
+    pub start_board: Board, // Posición inicial de las partidas contra la IA (por defecto la estándar)
+    // Si está activa, la regla de los 50 movimientos se reclama con la tecla T
+    // y la partida solo termina sola a los 75 movimientos
+    pub seventy_five_move_rule: bool,
+    pub book: Option<Arc<OpeningBook>>, // Libro de aperturas de la IA
+    pub tablebases: Option<Arc<Tablebases>>, // Tablas de finales Syzygy de la IA
+}
+
+// Partidas cargadas de un PGN y posición que se está mostrando
+struct Replay {
+    games: Vec<GameRecord>,
+    game: usize,     // Índice de la partida mostrada
+    ply: usize,      // Medios movimientos jugados en el tablero
+    caption: String, // Jugadores, número de jugada y última jugada en SAN
+}
+
+// Búsqueda de la IA en curso en otro hilo
+struct AiSearch {
+    receiver: mpsc::Receiver<Option<Move>>,
+    stop: Arc<AtomicBool>, // Cancela la búsqueda (volver al menú, fin de partida o cerrar la ventana)
+}
+
 pub struct GameState {
     resources: Resources,
     board: Board,
-    turn: PieceColor,
+    options: GameOptions,
     selected_square: Option<(usize, usize)>,
-    valid_moves_for_selected: Vec<(usize, usize)>,
+    valid_moves_for_selected: Vec<Move>,
+    pending_promotion: Vec<Move>, // Coronaciones entre las que el jugador debe elegir
     player_color: PieceColor,
-    game_over: bool,
-    winner: Option<PieceColor>,
+    result: Option<GameResult>, // Some(...) cuando la partida ha terminado
+    position_history: Vec<u64>, // Hashes de todas las posiciones de la partida, para detectar repeticiones
+    record: GameRecord, // Etiquetas, posición de partida y jugadas, para exportar a PGN
+    saved_game_path: Option<PathBuf>, // Archivo donde se guardó la partida al terminar
+    move_input: Option<String>, // Jugada en SAN que se está escribiendo (Enter abre el cuadro)
+    move_input_error: Option<String>,
+    ai_search: Option<AiSearch>, // Some(...) mientras la IA piensa
+    ai_table: Arc<Mutex<TranspositionTable>>, // Tabla de transposiciones de la IA, compartida con su hilo
+    difficulty: Difficulty, // Nivel de la IA, el último elegido en el menú
+    color_choice: ColorChoice, // Color elegido para la próxima partida contra la IA o como anfitrión
     
     // Menú y Red
     mode: AppMode,
     game_type: GameType,
     network_client: Option<NetworkClient>,
+    is_host: bool, // En red: si este jugador hospeda la partida
     host_listener: Option<mpsc::Receiver<TcpStream>>, // Canal para recibir stream aceptado
     host_ip: String,
     join_ip_input: String,
+
+    // Repaso de partidas PGN
+    pgn_path_input: String,
+    load_error: Option<String>,
+    replay: Option<Replay>,
 }
 
 impl GameState {
-    pub fn new(ctx: &mut Context) -> GameResult<Self> {
+    pub fn new(ctx: &mut Context, options: GameOptions) -> ggez::GameResult<Self> {
         let resources = Resources::new(ctx)?;
         let board = Board::new();
-        
+        let record = GameRecord::new(board.clone());
+
         Ok(Self {
             resources,
             board,
-            turn: PieceColor::White,
+            record,
+            saved_game_path: None,
+            options,
             selected_square: None,
             valid_moves_for_selected: Vec::new(),
+            pending_promotion: Vec::new(),
             player_color: PieceColor::White,
-            game_over: false,
-            winner: None,
+            result: None,
+            position_history: Vec::new(),
+            move_input: None,
+            move_input_error: None,
+            ai_search: None,
+            ai_table: Arc::new(Mutex::new(TranspositionTable::default())),
+            difficulty: Difficulty::default(),
+            color_choice: ColorChoice::Random,
             mode: AppMode::Menu,
             game_type: GameType::LocalAI,
             network_client: None,
+            is_host: false,
             host_listener: None,
             host_ip: String::new(),
             join_ip_input: String::new(),
+            pgn_path_input: String::new(),
+            load_error: None,
+            replay: None,
         })
     }
 
     fn reset_game(&mut self, player_color: PieceColor, game_type: GameType) {
-        self.board = Board::new();
-        self.turn = PieceColor::White;
+        self.cancel_ai_search();
+        // Tabla nueva en lugar de vaciarla: un hilo cancelado puede tenerla aún bloqueada
+        self.ai_table = Arc::new(Mutex::new(TranspositionTable::default()));
+        // En red ambos jugadores parten siempre de la posición estándar
+        self.board = match game_type {
+            GameType::LocalAI => self.options.start_board.clone(),
+            GameType::Multiplayer => Board::new(),
+        };
         self.selected_square = None;
         self.valid_moves_for_selected.clear();
+        self.pending_promotion.clear();
         self.player_color = player_color;
-        self.game_over = false;
-        self.winner = None;
+        self.result = None;
+        self.position_history = vec![self.board.hash];
+        self.move_input = None;
+        self.move_input_error = None;
         self.game_type = game_type;
         self.mode = AppMode::Playing;
+        self.record = self.new_record();
+        self.saved_game_path = None;
+    }
+
+    // Registro vacío de la partida que empieza, con las etiquetas PGN de jugadores y modo
+    fn new_record(&self) -> GameRecord {
+        let mut record = GameRecord::new(self.board.clone());
+        record.set_tag("Date", &pgn::today());
+
+        let ai_name = format!("IA ({})", self.difficulty);
+        let (me, opponent, mode) = match self.game_type {
+            GameType::LocalAI => ("Jugador", ai_name.as_str(), "Contra la IA"),
+            GameType::Multiplayer if self.is_host => ("Anfitrión", "Invitado", "Red local (anfitrión)"),
+            GameType::Multiplayer => ("Invitado", "Anfitrión", "Red local (invitado)"),
+        };
+        let (white, black) = match self.player_color {
+            PieceColor::White => (me, opponent),
+            PieceColor::Black => (opponent, me),
+        };
+        record.set_tag("White", white);
+        record.set_tag("Black", black);
+        if self.game_type == GameType::LocalAI {
+            record.set_tag("Difficulty", &self.difficulty.to_string());
+        }
+        if self.game_type == GameType::Multiplayer {
+            let address = if self.is_host { &self.host_ip } else { &self.join_ip_input };
+            record.set_tag("Site", &format!("Red local ({})", address));
+        }
+        record.set_tag("Mode", mode);
+        record
+    }
+
+    // Copia el PGN de la partida (terminada o en curso) al portapapeles y lo imprime
+    fn copy_pgn(&self) {
+        let pgn = self.record.to_pgn();
+        println!("{}", pgn);
+        if let Ok(mut clipboard) = arboard::Clipboard::new() {
+            let _ = clipboard.set_text(&pgn);
+        }
     }
 
     fn get_view_coords(&self, x: usize, y: usize) -> (usize, usize) {
@@ -92,6 +248,308 @@ impl GameState {
         }
     }
 
+    // Aplica una jugada venga de donde venga (jugador, IA o red) y comprueba si la partida terminó.
+    // Devuelve false, sin tocar nada, si la jugada no es legal.
+    fn apply_move(&mut self, ctx: &mut Context, mv: Move) -> bool {
+        let Some(mv) = self.board.move_piece(mv) else {
+            return false;
+        };
+        self.record.moves.push(mv);
+        self.position_history.push(self.board.hash);
+        let _ = self.resources.move_sound.play(ctx);
+
+        self.selected_square = None;
+        self.valid_moves_for_selected.clear();
+        self.pending_promotion.clear();
+
+        // Verificar Jaque Mate, Ahogado, material insuficiente, repetición y regla de 50/75 movimientos
+        if let Some(result) = self.board.outcome() {
+            self.end_game(result);
+        } else if self.board.repetition_count(&self.position_history) >= 3 {
+            self.end_game(GameResult::Draw(EndReason::ThreefoldRepetition));
+        } else if self.board.is_seventy_five_move_draw() {
+            self.end_game(GameResult::Draw(EndReason::SeventyFiveMoveRule));
+        } else if self.board.is_fifty_move_draw() && !self.options.seventy_five_move_rule {
+            self.end_game(GameResult::Draw(EndReason::FiftyMoveRule));
+        }
+        true
+    }
+
+    // Aplica una jugada del jugador local y la comunica al rival en red
+    fn play_player_move(&mut self, ctx: &mut Context, mv: Move) {
+        // Enviar movimiento si es Multijugador
+        if self.game_type == GameType::Multiplayer
+            && let Some(client) = &mut self.network_client {
+            client.send(NetworkMessage::Move(mv));
+        }
+
+        // Las jugadas del jugador salen del generador de jugadas legales
+        let _ = self.apply_move(ctx, mv);
+    }
+
+    // Juega la jugada escrita por teclado; si no es válida el cuadro sigue abierto con el error
+    fn submit_move_input(&mut self, ctx: &mut Context) {
+        let Some(text) = self.move_input.clone() else {
+            return;
+        };
+        match san::parse_san(&mut self.board, &text) {
+            Ok(mv) => {
+                self.move_input = None;
+                self.move_input_error = None;
+                self.play_player_move(ctx, mv);
+            }
+            Err(e) => self.move_input_error = Some(e.to_string()),
+        }
+    }
+
+    fn end_game(&mut self, result: GameResult) {
+        self.cancel_ai_search();
+        println!("Fin de la partida: {}", result);
+        self.result = Some(result);
+
+        // Guardar automáticamente la partida terminada
+        self.record.result = Some(result);
+        match self.record.save_to_dir(Path::new(pgn::GAMES_DIR)) {
+            Ok(path) => {
+                println!("Partida guardada en {}", path.display());
+                self.saved_game_path = Some(path);
+            }
+            Err(e) => println!("No se pudo guardar la partida: {}", e),
+        }
+    }
+
+    // Lanza la búsqueda de la IA en otro hilo para que la ventana siga respondiendo
+    fn start_ai_search(&mut self) {
+        let board = self.board.clone();
+        let history = self.position_history.clone();
+        let table = self.ai_table.clone();
+        let difficulty = self.difficulty;
+        let book = self.options.book.clone();
+        let tablebases = self.options.tablebases.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+
+        let thread_stop = stop.clone();
+        thread::spawn(move || {
+            let mut table = table.lock().unwrap();
+            // Si la búsqueda se canceló, nadie espera ya el resultado
+            let (best, tb_errors) = ai::get_best_move(&board, &history, difficulty, book.as_deref(), tablebases.as_deref(), &mut table, &thread_stop);
+            for error in tb_errors {
+                println!("{}", error);
+            }
+            let _ = tx.send(best);
+        });
+        self.ai_search = Some(AiSearch { receiver: rx, stop });
+    }
+
+    // Empieza una partida contra la IA del nivel elegido, con el color elegido para el jugador
+    fn start_ai_game(&mut self, difficulty: Difficulty) {
+        self.difficulty = difficulty;
+        self.reset_game(self.color_choice.resolve(), GameType::LocalAI);
+    }
+
+    // Fila de botones Blancas / Negras / Al azar, con la opción elegida resaltada
+    fn draw_color_picker(&self, ctx: &mut Context, canvas: &mut graphics::Canvas, y: f32) -> ggez::GameResult {
+        canvas.draw(&Text::new("Color:"), DrawParam::default().dest([200.0, y + 7.0]));
+        for (i, choice) in ColorChoice::ALL.into_iter().enumerate() {
+            let rect = ColorChoice::rect(i, y);
+            let (fill, text_color) = if choice == self.color_choice {
+                (Color::YELLOW, Color::BLACK)
+            } else {
+                (Color::new(0.3, 0.3, 0.3, 1.0), Color::WHITE)
+            };
+            let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, fill)?;
+            canvas.draw(&mesh, DrawParam::default());
+            let text = Text::new(choice.label());
+            canvas.draw(&text, DrawParam::default().dest([rect.x + 10.0, rect.y + 7.0]).color(text_color));
+        }
+        Ok(())
+    }
+
+    // Cambia el color elegido si el clic cae en uno de los botones de la fila `y`
+    fn click_color_picker(&mut self, x: f32, y: f32, row_y: f32) -> bool {
+        let clicked = (0..ColorChoice::ALL.len()).find(|&i| ColorChoice::rect(i, row_y).contains([x, y]));
+        if let Some(i) = clicked {
+            self.color_choice = ColorChoice::ALL[i];
+        }
+        clicked.is_some()
+    }
+
+    // Atajos del selector de color: B (Blancas), N (Negras) y A (al azar)
+    fn key_color_picker(&mut self, keycode: Option<KeyCode>) {
+        match keycode {
+            Some(KeyCode::B) => self.color_choice = ColorChoice::White,
+            Some(KeyCode::N) => self.color_choice = ColorChoice::Black,
+            Some(KeyCode::A) => self.color_choice = ColorChoice::Random,
+            _ => {}
+        }
+    }
+
+    // Detiene la búsqueda en curso, si la hay, y descarta su resultado
+    fn cancel_ai_search(&mut self) {
+        if let Some(search) = self.ai_search.take() {
+            search.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn resign(&mut self) {
+        if self.game_type == GameType::Multiplayer
+            && let Some(client) = &mut self.network_client {
+            client.send(NetworkMessage::Resign);
+        }
+        self.end_game(GameResult::win_for(self.player_color.opposite(), EndReason::Resignation));
+    }
+
+    // Las tablas por la regla de los 50 movimientos se reclaman en el turno propio; en red también
+    // terminan la partida del rival
+    fn claim_fifty_move_draw(&mut self) {
+        if self.result.is_some() || !self.board.is_fifty_move_draw() || self.board.side_to_move != self.player_color {
+            return;
+        }
+        if self.game_type == GameType::Multiplayer
+            && let Some(client) = &mut self.network_client {
+            client.send(NetworkMessage::ClaimFiftyMoveDraw);
+        }
+        self.end_game(GameResult::Draw(EndReason::FiftyMoveRule));
+    }
+
+    // Rectángulo de cada opción del selector de coronación
+    fn promotion_option_rect(index: usize) -> Rect {
+        let start_x = (SCREEN_SIZE.0 - 4.0 * CELL_SIZE) / 2.0;
+        let y = (SCREEN_SIZE.1 - CELL_SIZE) / 2.0;
+        Rect::new(start_x + index as f32 * CELL_SIZE, y, CELL_SIZE, CELL_SIZE)
+    }
+
+    // Lee el archivo PGN indicado y pasa a repasar su primera partida
+    fn load_pgn_file(&mut self) {
+        let path = self.pgn_path_input.trim();
+        let games = fs::read_to_string(path)
+            .map_err(|e| format!("No se pudo leer {}: {}", path, e))
+            .and_then(|text| pgn::parse_pgn(&text).map_err(|e| e.to_string()));
+        match games {
+            Ok(games) if games.is_empty() => self.load_error = Some("El archivo no contiene partidas".to_string()),
+            Ok(games) => {
+                println!("{} partida(s) cargada(s) de {}", games.len(), path);
+                self.replay = Some(Replay { games, game: 0, ply: 0, caption: String::new() });
+                self.load_error = None;
+                self.player_color = PieceColor::White;
+                self.selected_square = None;
+                self.valid_moves_for_selected.clear();
+                self.result = None;
+                self.show_replay_position(0, 0);
+                self.mode = AppMode::Replay;
+            }
+            Err(e) => {
+                println!("{}", e);
+                self.load_error = Some(e);
+            }
+        }
+    }
+
+    // Coloca en el tablero la partida `game` tras `ply` medios movimientos (se ajustan a los límites)
+    fn show_replay_position(&mut self, game: usize, ply: usize) {
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+        let game = game.min(replay.games.len() - 1);
+        let record = &replay.games[game];
+        let ply = ply.min(record.moves.len());
+
+        let mut board = record.start.clone();
+        let mut last_move = String::new();
+        for (i, &mv) in record.moves[..ply].iter().enumerate() {
+            if i + 1 == ply {
+                let dots = if board.side_to_move == PieceColor::White { "." } else { "..." };
+                let number = board.fullmove_number;
+                last_move = format!("{}{} {}", number, dots, san::to_san(&mut board, mv));
+            }
+            board.make_move(mv);
+        }
+
+        let tag = |name: &str| {
+            record.tags.iter().find(|(tag, _)| tag == name).map_or("?", |(_, value)| value.as_str())
+        };
+        replay.caption = format!(
+            "Partida {}/{}: {} - {} ({})   Jugada {}/{} {}",
+            game + 1,
+            replay.games.len(),
+            tag("White"),
+            tag("Black"),
+            pgn::result_token(record.result),
+            ply,
+            record.moves.len(),
+            last_move,
+        );
+        replay.game = game;
+        replay.ply = ply;
+        self.board = board;
+    }
+
+    fn draw_squares(&self, ctx: &mut Context, canvas: &mut graphics::Canvas) -> ggez::GameResult {
+        for y in 0..BOARD_SIZE {
+            for x in 0..BOARD_SIZE {
+                let (draw_x, draw_y) = self.get_view_coords(x, y);
+
+                let rect = Rect::new(
+                    draw_x as f32 * CELL_SIZE,
+                    draw_y as f32 * CELL_SIZE,
+                    CELL_SIZE,
+                    CELL_SIZE,
+                );
+
+                let color = if (x + y) % 2 == 0 {
+                    WHITE_COLOR
+                } else {
+                    BLACK_COLOR
+                };
+
+                let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, color)?;
+                canvas.draw(&mesh, DrawParam::default());
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_highlight(&self, ctx: &mut Context, canvas: &mut graphics::Canvas, pos: (usize, usize), color: Color) -> ggez::GameResult {
+        let (draw_x, draw_y) = self.get_view_coords(pos.0, pos.1);
+        let rect = Rect::new(
+            draw_x as f32 * CELL_SIZE,
+            draw_y as f32 * CELL_SIZE,
+            CELL_SIZE,
+            CELL_SIZE,
+        );
+        let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, color)?;
+        canvas.draw(&mesh, DrawParam::default());
+        Ok(())
+    }
+
+    fn draw_pieces(&self, canvas: &mut graphics::Canvas) {
+        for y in 0..BOARD_SIZE {
+            for x in 0..BOARD_SIZE {
+                if let Some(piece) = self.board.piece_at((x, y)) {
+                    let (draw_x, draw_y) = self.get_view_coords(x, y);
+
+                    if let Some(image) = self.resources.pieces.get(&(piece.piece_type, piece.color)) {
+                        let target_height = CELL_SIZE * 0.85;
+                        let scale = target_height / image.height() as f32;
+
+                        let img_width = image.width() as f32 * scale;
+                        let img_height = image.height() as f32 * scale;
+
+                        let dest_x = (draw_x as f32 * CELL_SIZE) + (CELL_SIZE - img_width) / 2.0;
+                        let dest_y = ((draw_y + 1) as f32 * CELL_SIZE) - img_height - (CELL_SIZE * 0.05);
+
+                        let draw_params = DrawParam::default()
+                            .dest([dest_x, dest_y])
+                            .scale([scale, scale]);
+
+                        canvas.draw(image, draw_params);
+                    }
+                }
+            }
+        }
+    }
+
     fn start_host(&mut self) {
         if let Ok(ip) = local_ip() {
             self.host_ip = format!("{}:8080", ip);
@@ -106,13 +564,13 @@ impl GameState {
             let (tx, rx) = mpsc::channel();
             
             thread::spawn(move || {
-                if let Ok(listener) = TcpListener::bind(address) {
-                    if let Ok((stream, _)) = listener.accept() {
-                        let _ = tx.send(stream);
-                    }
+                if let Ok(listener) = TcpListener::bind(address)
+                    && let Ok((stream, _)) = listener.accept() {
+                    let _ = tx.send(stream);
                 }
             });
             self.host_listener = Some(rx);
+            self.is_host = true;
             self.mode = AppMode::HostWait;
         } else {
             println!("Error al obtener IP local");
@@ -125,6 +583,7 @@ impl GameState {
             println!("Conectado a {}", self.join_ip_input);
             let client = NetworkClient::new(stream);
             self.network_client = Some(client);
+            self.is_host = false;
             self.mode = AppMode::Playing;
             self.game_type = GameType::Multiplayer;
         } else {
@@ -135,38 +594,35 @@ impl GameState {
 
 impl event::EventHandler<ggez::GameError> for GameState {
     // Implementar Copy para AppMode y GameType
-    fn update(&mut self, ctx: &mut Context) -> GameResult {
+    fn update(&mut self, ctx: &mut Context) -> ggez::GameResult {
         let mode = self.mode; // Acceder a copia
         match mode {
-            AppMode::Menu => {},
+            AppMode::Menu | AppMode::AiSetup => {},
             AppMode::HostWait => {
                 // Verificar si el cliente se conectó
                 // necesario limitar el alcance del préstamo
                 let mut new_stream: Option<TcpStream> = None;
-                if let Some(rx) = &self.host_listener {
-                    if let Ok(stream) = rx.try_recv() {
-                        new_stream = Some(stream);
-                    }
+                if let Some(rx) = &self.host_listener
+                    && let Ok(stream) = rx.try_recv() {
+                    new_stream = Some(stream);
                 }
 
                 if let Some(stream) = new_stream {
                     println!("¡Cliente conectado!");
                     let mut client = NetworkClient::new(stream);
                     
-                    // Asignar colores
-                    let mut rng = rand::thread_rng();
-                    let my_color = if rng.gen_bool(0.5) { PieceColor::White } else { PieceColor::Black };
-                    let client_color = my_color.opposite();
-                    
+                    // Asignar colores según lo elegido por el anfitrión
+                    let my_color = self.color_choice.resolve();
+
                     // Enviar saludo (Handshake)
-                    client.send(NetworkMessage::Handshake { color: client_color });
+                    client.send(NetworkMessage::Handshake { host_color: my_color });
                     
                     self.network_client = Some(client);
                     self.reset_game(my_color, GameType::Multiplayer);
                     self.host_listener = None;
                 }
             },
-            AppMode::JoinInput => {},
+            AppMode::JoinInput | AppMode::LoadInput | AppMode::Replay => {},
             AppMode::Playing => {
                 // Manejar mensajes de red
                 let mut messages = Vec::new();
@@ -178,47 +634,82 @@ impl event::EventHandler<ggez::GameError> for GameState {
 
                 for msg in messages {
                     match msg {
-                        NetworkMessage::Handshake { color } => {
+                        NetworkMessage::Handshake { host_color } => {
+                            let color = host_color.opposite();
                             println!("Mensaje recibido: Eres {:?}", color);
                             self.reset_game(color, GameType::Multiplayer);
                         },
-                        NetworkMessage::Move { from, to } => {
-                            println!("Movimiento recibido: {:?} -> {:?}", from, to);
-                            self.board.move_piece(from, to);
-                            // Reproducir sonido
-                            let _ = self.resources.move_sound.play(ctx);
-                            self.turn = self.turn.opposite();
-                            // Verificar fin del juego
-                            if self.board.is_checkmate(self.turn) {
-                                self.game_over = true;
-                                self.winner = Some(self.turn.opposite());
+                        NetworkMessage::Move(mv) => {
+                            if self.result.is_some() {
+                                continue;
+                            }
+                            // Una jugada ilegal, o fuera de turno, corta la conexión y termina la partida
+                            let legal = self.board.clone().move_piece(mv);
+                            match legal {
+                                Some(mv) if self.board.side_to_move != self.player_color => {
+                                    println!("Movimiento recibido: {}", san::to_san(&mut self.board, mv));
+                                    let _ = self.apply_move(ctx, mv);
+                                }
+                                _ => {
+                                    println!("Jugada ilegal recibida del rival: {}; se corta la conexión", mv);
+                                    self.network_client = None;
+                                    self.end_game(GameResult::win_for(self.player_color, EndReason::Disconnect));
+                                }
+                            }
+                        },
+                        NetworkMessage::Resign => {
+                            println!("El rival abandonó la partida");
+                            if self.result.is_none() {
+                                self.end_game(GameResult::win_for(self.player_color, EndReason::Resignation));
+                            }
+                        }
+                        NetworkMessage::ClaimFiftyMoveDraw => {
+                            // Solo vale si de verdad se cumple la regla y le toca mover al rival
+                            if self.result.is_none()
+                                && self.board.is_fifty_move_draw()
+                                && self.board.side_to_move != self.player_color {
+                                println!("El rival reclamó tablas por la regla de los 50 movimientos");
+                                self.end_game(GameResult::Draw(EndReason::FiftyMoveRule));
+                            } else if self.result.is_none() {
+                                println!("El rival reclamó tablas por la regla de los 50 movimientos sin derecho; se ignora");
                             }
                         }
                     }
                 }
 
-                if self.game_over {
+                if self.result.is_some() {
                     return Ok(());
                 }
 
-                if self.game_type == GameType::LocalAI && self.turn != self.player_color {
-                     // Lógica de IA
-                     let best_move = ai::get_best_move(&self.board, self.turn);
-                     if let Some(((from_x, from_y), (to_x, to_y))) = best_move {
-                        self.board.move_piece((from_x, from_y), (to_x, to_y));
-                        let _ = self.resources.move_sound.play(ctx);
-                        self.turn = self.turn.opposite();
-                        if self.board.is_checkmate(self.turn) {
-                            self.game_over = true;
-                            self.winner = Some(self.turn.opposite());
+                // El rival cerró la conexión a mitad de partida
+                if self.game_type == GameType::Multiplayer
+                    && self.network_client.as_ref().is_some_and(|client| !client.is_connected()) {
+                    self.end_game(GameResult::win_for(self.player_color, EndReason::Disconnect));
+                    return Ok(());
+                }
+
+                if self.game_type == GameType::LocalAI && self.board.side_to_move != self.player_color {
+                    // Lógica de IA: se lanza la búsqueda y se recoge la jugada cuando termine
+                    let Some(search) = &self.ai_search else {
+                        self.start_ai_search();
+                        return Ok(());
+                    };
+                    match search.receiver.try_recv() {
+                        Ok(best) => {
+                            self.ai_search = None;
+                            match best {
+                                Some(mv) => {
+                                    let _ = self.apply_move(ctx, mv);
+                                }
+                                // Sin jugadas: la posición ya estaba terminada
+                                None => if let Some(result) = self.board.outcome() {
+                                    self.end_game(result);
+                                },
+                            }
                         }
-                    } else {
-                         if self.board.is_checkmate(self.turn) {
-                             self.game_over = true;
-                             self.winner = Some(self.turn.opposite());
-                         } else {
-                             self.game_over = true; 
-                         }
+                        Err(mpsc::TryRecvError::Empty) => {}
+                        // El hilo terminó sin responder: se vuelve a intentar
+                        Err(mpsc::TryRecvError::Disconnected) => self.ai_search = None,
                     }
                 }
             }
@@ -226,7 +717,7 @@ impl event::EventHandler<ggez::GameError> for GameState {
         Ok(())
     }
 
-    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+    fn draw(&mut self, ctx: &mut Context) -> ggez::GameResult {
         let mut canvas = graphics::Canvas::from_frame(ctx, Color::BLACK);
 
         let mode = self.mode;
@@ -243,10 +734,68 @@ impl event::EventHandler<ggez::GameError> for GameState {
 
                 let join = Text::new("3. Unirse al Juego");
                 canvas.draw(&join, DrawParam::default().dest([350.0, 400.0]));
+
+                let load = Text::new("4. Ver partida (PGN)");
+                canvas.draw(&load, DrawParam::default().dest([350.0, 450.0]));
+            },
+            AppMode::AiSetup => {
+                let title = Text::new("Jugador vs IA");
+                canvas.draw(&title, DrawParam::default().dest([300.0, 120.0]).scale([1.5, 1.5]));
+
+                self.draw_color_picker(ctx, &mut canvas, AI_COLOR_ROW_Y)?;
+
+                // El último nivel elegido aparece resaltado
+                let heading = Text::new("Nivel de la IA:");
+                canvas.draw(&heading, DrawParam::default().dest([200.0, AI_LEVELS_Y - 50.0]));
+                for (i, level) in Difficulty::ALL.into_iter().enumerate() {
+                    let color = if level == self.difficulty { Color::YELLOW } else { Color::WHITE };
+                    let text = Text::new(format!("{}. {}", i + 1, level));
+                    canvas.draw(&text, DrawParam::default().dest([350.0, AI_LEVELS_Y + 50.0 * i as f32]).color(color));
+                }
+
+                let hint = Text::new("Color: clic o teclas B / N / A\nNivel: clic o teclas 1-5 para empezar (Esc para volver)");
+                canvas.draw(&hint, DrawParam::default().dest([200.0, 600.0]));
+            },
+            AppMode::LoadInput => {
+                let text = Text::new(format!("Ruta del archivo PGN:\n{}", self.pgn_path_input));
+                canvas.draw(&text, DrawParam::default().dest([100.0, 350.0]).scale([1.5, 1.5]));
+                let hint = Text::new("Escriba la ruta y presione Enter (Esc para volver)");
+                canvas.draw(&hint, DrawParam::default().dest([100.0, 450.0]));
+                if let Some(error) = &self.load_error {
+                    let error = Text::new(error.as_str());
+                    canvas.draw(&error, DrawParam::default().dest([100.0, 500.0]).color(Color::RED));
+                }
+            },
+            AppMode::Replay => {
+                self.draw_squares(ctx, &mut canvas)?;
+
+                // Resaltar la última jugada
+                if let Some(replay) = &self.replay
+                    && replay.ply > 0 {
+                    let mv = replay.games[replay.game].moves[replay.ply - 1];
+                    self.draw_highlight(ctx, &mut canvas, mv.from, HIGHLIGHT_COLOR)?;
+                    self.draw_highlight(ctx, &mut canvas, mv.to, HIGHLIGHT_COLOR)?;
+                }
+
+                self.draw_pieces(&mut canvas);
+
+                if let Some(replay) = &self.replay {
+                    let area = Rect::new(0.0, SCREEN_SIZE.1 - 70.0, SCREEN_SIZE.0, 70.0);
+                    let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), area, Color::new(0.0, 0.0, 0.0, 0.75))?;
+                    canvas.draw(&mesh, DrawParam::default());
+                    canvas.draw(&Text::new(replay.caption.as_str()), DrawParam::default().dest([20.0, area.y + 12.0]));
+                    let hint = Text::new("Izquierda/Derecha: jugadas  ·  Inicio/Fin  ·  Arriba/Abajo: partidas  ·  Esc: menú");
+                    canvas.draw(&hint, DrawParam::default().dest([20.0, area.y + 42.0]));
+                }
             },
             AppMode::HostWait => {
                 let text = Text::new(format!("Esperando jugador...\nCódigo (IP): {}", self.host_ip));
                 canvas.draw(&text, DrawParam::default().dest([250.0, 350.0]).scale([1.5, 1.5]));
+
+                // El color se puede cambiar hasta que se conecte el invitado
+                self.draw_color_picker(ctx, &mut canvas, HOST_COLOR_ROW_Y)?;
+                let hint = Text::new("Su color: clic o teclas B / N / A");
+                canvas.draw(&hint, DrawParam::default().dest([250.0, HOST_COLOR_ROW_Y + 50.0]));
             },
             AppMode::JoinInput => {
                  let text = Text::new(format!("Ingrese Código (IP:Puerto):\n{}", self.join_ip_input));
@@ -255,80 +804,75 @@ impl event::EventHandler<ggez::GameError> for GameState {
                 canvas.draw(&hint, DrawParam::default().dest([250.0, 450.0]));
             },
             AppMode::Playing => {
-                // ... Existing Draw Logic ...
                 // Dibujar Tablero
-                for y in 0..BOARD_SIZE {
-                    for x in 0..BOARD_SIZE {
-                        let (draw_x, draw_y) = self.get_view_coords(x, y);
-
-                        let rect = Rect::new(
-                            draw_x as f32 * CELL_SIZE,
-                            draw_y as f32 * CELL_SIZE,
-                            CELL_SIZE,
-                            CELL_SIZE,
-                        );
-                        
-                        let color = if (x + y) % 2 == 0 {
-                            WHITE_COLOR
-                        } else {
-                            BLACK_COLOR
-                        };
+                self.draw_squares(ctx, &mut canvas)?;
 
-                        let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, color)?;
-                        canvas.draw(&mesh, DrawParam::default());
-                    }
+                // Resaltar Casilla Seleccionada
+                if let Some(selected) = self.selected_square {
+                    self.draw_highlight(ctx, &mut canvas, selected, HIGHLIGHT_COLOR)?;
                 }
 
-                // Resaltar Casilla Seleccionada
-                if let Some((sx, sy)) = self.selected_square {
-                     let (draw_x, draw_y) = self.get_view_coords(sx, sy);
-                     let rect = Rect::new(
-                        draw_x as f32 * CELL_SIZE,
-                        draw_y as f32 * CELL_SIZE,
-                        CELL_SIZE,
-                        CELL_SIZE,
-                    );
+                // Resaltar Movimientos Válidos
+                for mv in &self.valid_moves_for_selected {
+                    self.draw_highlight(ctx, &mut canvas, mv.to, VALID_MOVE_COLOR)?;
+                }
+
+                // Dibujar Piezas
+                self.draw_pieces(&mut canvas);
+
+                // Selector de coronación
+                for (i, mv) in self.pending_promotion.iter().enumerate() {
+                    let rect = Self::promotion_option_rect(i);
                     let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, HIGHLIGHT_COLOR)?;
                     canvas.draw(&mesh, DrawParam::default());
+
+                    if let Some(piece_type) = mv.promotion
+                        && let Some(image) = self.resources.pieces.get(&(piece_type, self.player_color)) {
+                        let scale = CELL_SIZE * 0.85 / image.height() as f32;
+                        let dest_x = rect.x + (CELL_SIZE - image.width() as f32 * scale) / 2.0;
+                        let dest_y = rect.y + (CELL_SIZE - image.height() as f32 * scale) / 2.0;
+                        canvas.draw(image, DrawParam::default().dest([dest_x, dest_y]).scale([scale, scale]));
+                    }
+                }
+
+                // Indicador de que la IA está pensando
+                if self.ai_search.is_some() {
+                    let area = Rect::new(SCREEN_SIZE.0 - 170.0, 10.0, 160.0, 36.0);
+                    let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), area, Color::new(0.0, 0.0, 0.0, 0.75))?;
+                    canvas.draw(&mesh, DrawParam::default());
+                    let text = Text::new("Pensando...");
+                    canvas.draw(&text, DrawParam::default().dest([area.x + 15.0, area.y + 8.0]).scale([1.3, 1.3]));
                 }
 
-                // Resaltar Movimientos Válidos
-                for &(mx, my) in &self.valid_moves_for_selected {
-                    let (draw_x, draw_y) = self.get_view_coords(mx, my);
-                     let rect = Rect::new(
-                        draw_x as f32 * CELL_SIZE,
-                        draw_y as f32 * CELL_SIZE,
-                        CELL_SIZE,
-                        CELL_SIZE,
-                    );
-                    let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, VALID_MOVE_COLOR)?;
+                // Cuadro de entrada de jugadas por teclado
+                if let Some(input) = &self.move_input {
+                    let area = Rect::new(0.0, SCREEN_SIZE.1 - 70.0, SCREEN_SIZE.0, 70.0);
+                    let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), area, Color::new(0.0, 0.0, 0.0, 0.75))?;
                     canvas.draw(&mesh, DrawParam::default());
+
+                    let text = Text::new(format!("Jugada (SAN): {}_", input));
+                    canvas.draw(&text, DrawParam::default().dest([20.0, area.y + 10.0]).scale([1.5, 1.5]));
+                    let hint = match &self.move_input_error {
+                        Some(error) => error.clone(),
+                        None => "Enter para jugar, Esc para cancelar".to_string(),
+                    };
+                    canvas.draw(&Text::new(hint), DrawParam::default().dest([20.0, area.y + 45.0]));
                 }
 
-                // Dibujar Piezas
-                for y in 0..BOARD_SIZE {
-                    for x in 0..BOARD_SIZE {
-                        if let Some(piece) = self.board.grid[y][x] {
-                            let (draw_x, draw_y) = self.get_view_coords(x, y);
-
-                            if let Some(image) = self.resources.pieces.get(&(piece.piece_type, piece.color)) {
-                                let target_height = CELL_SIZE * 0.85; 
-                                let scale = target_height / image.height() as f32;
-                                
-                                let img_width = image.width() as f32 * scale;
-                                let img_height = image.height() as f32 * scale;
-                                
-                                let dest_x = (draw_x as f32 * CELL_SIZE) + (CELL_SIZE - img_width) / 2.0;
-                                let dest_y = ((draw_y + 1) as f32 * CELL_SIZE) - img_height - (CELL_SIZE * 0.05); 
-
-                                let draw_params = DrawParam::default()
-                                    .dest([dest_x, dest_y])
-                                    .scale([scale, scale]);
-                                
-                                canvas.draw(image, draw_params);
-                            }
-                        }
-                    }
+                // Resultado de la partida
+                if let Some(result) = self.result {
+                    let banner = Rect::new(0.0, 330.0, SCREEN_SIZE.0, 140.0);
+                    let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), banner, Color::new(0.0, 0.0, 0.0, 0.75))?;
+                    canvas.draw(&mesh, DrawParam::default());
+
+                    let text = Text::new(result.to_string());
+                    canvas.draw(&text, DrawParam::default().dest([200.0, 360.0]).scale([2.0, 2.0]));
+                    let saved = match &self.saved_game_path {
+                        Some(path) => format!("Guardada en {}  ·  ", path.display()),
+                        None => String::new(),
+                    };
+                    let hint = Text::new(format!("{}P: copiar PGN  ·  Clic para volver al menú", saved));
+                    canvas.draw(&hint, DrawParam::default().dest([40.0, 420.0]));
                 }
             }
         }
@@ -337,17 +881,127 @@ impl event::EventHandler<ggez::GameError> for GameState {
         Ok(())
     }
 
-    fn text_input_event(&mut self, _ctx: &mut Context, character: char) -> GameResult {
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) -> ggez::GameResult {
         if self.mode == AppMode::JoinInput {
              if character.is_control() {
                  return Ok(()); // Ignorar caracteres de control como Backspace aqui, manejar en key_down
              }
              self.join_ip_input.push(character);
         }
+        if self.mode == AppMode::LoadInput && !character.is_control() {
+            self.pgn_path_input.push(character);
+        }
+        if self.mode == AppMode::Playing
+            && let Some(input) = &mut self.move_input
+            && !character.is_control() {
+            input.push(character);
+            self.move_input_error = None;
+        }
         Ok(())
     }
 
-    fn key_down_event(&mut self, _ctx: &mut Context, input: ggez::input::keyboard::KeyInput, _repeated: bool) -> GameResult {
+    fn key_down_event(&mut self, ctx: &mut Context, input: ggez::input::keyboard::KeyInput, _repeated: bool) -> ggez::GameResult {
+        // Mientras se escribe una jugada, las teclas no actúan como atajos
+        if self.mode == AppMode::Playing && self.move_input.is_some() {
+            match input.keycode {
+                Some(KeyCode::Back) => {
+                    if let Some(text) = &mut self.move_input {
+                        text.pop();
+                    }
+                    self.move_input_error = None;
+                }
+                Some(KeyCode::Return) => self.submit_move_input(ctx),
+                Some(KeyCode::Escape) => {
+                    self.move_input = None;
+                    self.move_input_error = None;
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        // Escribir una jugada en SAN en lugar de usar el ratón
+        if self.mode == AppMode::Playing && self.result.is_none() && input.keycode == Some(KeyCode::Return)
+            && self.board.side_to_move == self.player_color && self.pending_promotion.is_empty() {
+            self.move_input = Some(String::new());
+            return Ok(());
+        }
+
+        if self.mode == AppMode::Playing && self.result.is_none() && input.keycode == Some(KeyCode::R) {
+            self.resign();
+        }
+
+        // Reclamar tablas por la regla de los 50 movimientos
+        if self.mode == AppMode::Playing && input.keycode == Some(KeyCode::T) {
+            self.claim_fifty_move_draw();
+        }
+
+        // Exportar la partida en PGN, también desde la pantalla final
+        if self.mode == AppMode::Playing && input.keycode == Some(KeyCode::P) {
+            self.copy_pgn();
+        }
+
+        if self.mode == AppMode::Playing && input.keycode == Some(KeyCode::F) {
+            // Copiar la posición actual (FEN) para compartirla o depurar
+            let fen = self.board.to_fen();
+            println!("FEN: {}", fen);
+            if let Ok(mut clipboard) = arboard::Clipboard::new() {
+                let _ = clipboard.set_text(&fen);
+            }
+        }
+
+        if matches!(self.mode, AppMode::AiSetup | AppMode::HostWait) {
+            self.key_color_picker(input.keycode);
+        }
+
+        if self.mode == AppMode::AiSetup {
+            let level = match input.keycode {
+                Some(KeyCode::Key1) => Some(Difficulty::Beginner),
+                Some(KeyCode::Key2) => Some(Difficulty::Easy),
+                Some(KeyCode::Key3) => Some(Difficulty::Medium),
+                Some(KeyCode::Key4) => Some(Difficulty::Hard),
+                Some(KeyCode::Key5) => Some(Difficulty::Maximum),
+                Some(KeyCode::Escape) => {
+                    self.mode = AppMode::Menu;
+                    None
+                }
+                _ => None,
+            };
+            if let Some(level) = level {
+                self.start_ai_game(level);
+            }
+        }
+
+        if self.mode == AppMode::LoadInput {
+            match input.keycode {
+                Some(KeyCode::Back) => {
+                    self.pgn_path_input.pop();
+                }
+                Some(KeyCode::Return) => self.load_pgn_file(),
+                Some(KeyCode::Escape) => self.mode = AppMode::Menu,
+                _ => {}
+            }
+        }
+
+        // Navegación por la partida cargada
+        if self.mode == AppMode::Replay
+            && let Some(replay) = &self.replay {
+            let (game, ply, last_game) = (replay.game, replay.ply, replay.games.len() - 1);
+            match input.keycode {
+                Some(KeyCode::Right) => self.show_replay_position(game, ply + 1),
+                Some(KeyCode::Left) => self.show_replay_position(game, ply.saturating_sub(1)),
+                Some(KeyCode::Home) => self.show_replay_position(game, 0),
+                Some(KeyCode::End) => self.show_replay_position(game, usize::MAX),
+                Some(KeyCode::Down) if game < last_game => self.show_replay_position(game + 1, 0),
+                Some(KeyCode::Up) if game > 0 => self.show_replay_position(game - 1, 0),
+                Some(KeyCode::Escape) => {
+                    self.replay = None;
+                    self.mode = AppMode::Menu;
+                }
+                _ => {}
+            }
+        }
+
         if self.mode == AppMode::JoinInput {
              match input.keycode {
                  Some(KeyCode::Back) => {
@@ -363,46 +1017,84 @@ impl event::EventHandler<ggez::GameError> for GameState {
         Ok(())
     }
 
+    // Al cerrar la ventana se cancela la búsqueda de la IA para no dejar el hilo trabajando
+    fn quit_event(&mut self, _ctx: &mut Context) -> ggez::GameResult<bool> {
+        self.cancel_ai_search();
+        Ok(false)
+    }
+
     fn mouse_button_down_event(
         &mut self,
         ctx: &mut Context,
         button: MouseButton,
         x: f32,
         y: f32,
-    ) -> GameResult {
+    ) -> ggez::GameResult {
         let mode = self.mode;
         match mode {
-            AppMode::Menu => {
-                // Determinar clics
-                // Hitboxes simples basados en posición de texto
-                // Play AI: 350, 300
-                // Host: 350, 350
-                // Join: 350, 400
-                if button == MouseButton::Left {
-                    if x > 350.0 && x < 600.0 {
-                        if y > 300.0 && y < 330.0 {
-                            // AI
-                            let mut rng = rand::thread_rng();
-                            let my_color = if rng.gen_bool(0.5) { PieceColor::White } else { PieceColor::Black };
-                            self.reset_game(my_color, GameType::LocalAI);
-                        } else if y > 350.0 && y < 380.0 {
-                            // Host
-                            self.start_host();
-                        } else if y > 400.0 && y < 430.0 {
-                            // Join
-                            self.mode = AppMode::JoinInput;
-                            self.join_ip_input.clear();
-                        }
-                    }
+            // Determinar clics
+            // Hitboxes simples basados en posición de texto
+            // Play AI: 350, 300
+            // Host: 350, 350
+            // Join: 350, 400
+            // PGN: 350, 450
+            AppMode::Menu if button == MouseButton::Left && x > 350.0 && x < 600.0 => {
+                if y > 300.0 && y < 330.0 {
+                    // AI: primero se elige el nivel
+                    self.mode = AppMode::AiSetup;
+                } else if y > 350.0 && y < 380.0 {
+                    // Host
+                    self.start_host();
+                } else if y > 400.0 && y < 430.0 {
+                    // Join
+                    self.mode = AppMode::JoinInput;
+                    self.join_ip_input.clear();
+                } else if y > 450.0 && y < 480.0 {
+                    // Ver partida PGN
+                    self.mode = AppMode::LoadInput;
+                    self.load_error = None;
                 }
             },
+            // Color: fila AI_COLOR_ROW_Y; niveles: 350, AI_LEVELS_Y + 50 * i
+            AppMode::AiSetup if button == MouseButton::Left => {
+                if self.click_color_picker(x, y, AI_COLOR_ROW_Y) || x <= 350.0 || x >= 600.0 {
+                    return Ok(());
+                }
+                let level = Difficulty::ALL.into_iter()
+                    .enumerate()
+                    .find(|&(i, _)| {
+                        let top = AI_LEVELS_Y + 50.0 * i as f32;
+                        y > top && y < top + 30.0
+                    });
+                if let Some((_, level)) = level {
+                    self.start_ai_game(level);
+                }
+            },
+            AppMode::HostWait if button == MouseButton::Left => {
+                self.click_color_picker(x, y, HOST_COLOR_ROW_Y);
+            },
             AppMode::Playing => {
-                if self.game_over {
+                if self.result.is_some() {
+                     self.cancel_ai_search();
                      self.mode = AppMode::Menu;
                      return Ok(());
                 }
 
-                if self.turn != self.player_color {
+                if self.board.side_to_move != self.player_color {
+                    return Ok(());
+                }
+
+                // Elegir pieza de coronación (un clic fuera cancela)
+                if !self.pending_promotion.is_empty() {
+                    if button == MouseButton::Left {
+                        let chosen = (0..self.pending_promotion.len())
+                            .find(|&i| Self::promotion_option_rect(i).contains([x, y]))
+                            .map(|i| self.pending_promotion[i]);
+                        self.pending_promotion.clear();
+                        if let Some(mv) = chosen {
+                            self.play_player_move(ctx, mv);
+                        }
+                    }
                     return Ok(());
                 }
 
@@ -418,34 +1110,22 @@ impl event::EventHandler<ggez::GameError> for GameState {
                     let (grid_x, grid_y) = self.get_view_coords(screen_grid_x, screen_grid_y);
 
                     // Si hay una pieza seleccionada y se clickea un movimiento válido
-                    if let Some(selected) = self.selected_square {
-                        if self.valid_moves_for_selected.contains(&(grid_x, grid_y)) {
-                            // Ejecutar movimiento
-                            self.board.move_piece(selected, (grid_x, grid_y));
-                            let _ = self.resources.move_sound.play(ctx);
-                            
-                            // Enviar movimiento si es Multijugador
-                            if self.game_type == GameType::Multiplayer {
-                                if let Some(client) = &mut self.network_client {
-                                    client.send(NetworkMessage::Move { from: selected, to: (grid_x, grid_y) });
-                                }
-                            }
-
-                            self.selected_square = None;
-                            self.valid_moves_for_selected.clear();
-                            self.turn = self.turn.opposite();
-
-                            // Verificar Jaque Mate
-                            if self.board.is_checkmate(self.turn) {
-                                self.game_over = true;
-                                self.winner = Some(self.turn.opposite());
-                            }
-                            return Ok(());
-                        }
+                    let candidates: Vec<Move> = self.valid_moves_for_selected.iter()
+                        .filter(|mv| mv.to == (grid_x, grid_y))
+                        .copied()
+                        .collect();
+                    if candidates.len() > 1 {
+                        // Varias jugadas al mismo destino: es una coronación
+                        self.pending_promotion = candidates;
+                        return Ok(());
+                    }
+                    if let Some(&mv) = candidates.first() {
+                        self.play_player_move(ctx, mv);
+                        return Ok(());
                     }
 
                     // Seleccionar pieza
-                    if let Some(piece) = self.board.grid[grid_y][grid_x] {
+                    if let Some(piece) = self.board.piece_at((grid_x, grid_y)) {
                         if piece.color == self.player_color {
                             self.selected_square = Some((grid_x, grid_y));
                             self.valid_moves_for_selected = self.board.get_valid_moves((grid_x, grid_y));
@@ -0,0 +1,28 @@
+// Reglas, motor y herramientas del ajedrez, sin dependencias gráficas.
+// La interfaz con ggez (ventana, recursos y sonidos) solo se compila con la característica `gui`.
+
+pub mod pieces;
+pub mod moves;
+pub mod board;
+pub mod result;
+mod zobrist;
+mod bitboard;
+mod book_keys;
+pub mod perft;
+pub mod bench;
+pub mod san;
+pub mod pgn;
+pub mod uci;
+pub mod ai;
+pub mod eval;
+pub mod transposition;
+pub mod book;
+pub mod syzygy;
+pub mod network;
+
+#[cfg(feature = "gui")]
+pub mod constants;
+#[cfg(feature = "gui")]
+pub mod resources;
+#[cfg(feature = "gui")]
+pub mod game;
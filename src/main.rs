@@ -1,20 +1,40 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use ggez::{conf, event, ContextBuilder};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-mod constants;
-mod resources;
-mod pieces;
-mod board;
-mod ai;
-mod game;
-mod network;
-
-use constants::SCREEN_SIZE;
-use game::GameState;
+use chess::board::Board;
+use chess::book::{BookMode, OpeningBook};
+use chess::constants::SCREEN_SIZE;
+use chess::game::{GameOptions, GameState};
+use chess::syzygy::Tablebases;
 
 fn main() {
+    // Uso: chess [--fen "<FEN>"] [--regla-75] [--libro <archivo.bin> | --sin-libro] [--libro-jugadas N] [--libro-mejor] [--syzygy <directorio>]
+    //   --fen: empezar las partidas contra la IA desde otra posición
+    //   --regla-75: la regla de los 50 movimientos se reclama (tecla T) y solo la de 75 es automática
+    //   --libro: libro de aperturas Polyglot de la IA (por defecto assets/book.bin); --sin-libro lo desactiva
+    //   --libro-jugadas: jugadas de cada bando en las que se consulta el libro
+    //   --libro-mejor: jugar siempre la jugada del libro con más peso en lugar de sortearla
+    //   --syzygy: directorio (o varios, separados como en PATH) con tablas de finales Syzygy para la IA
+    // Los subcomandos de consola (perft, bench, uci) están en el binario `chess-uci`: en Windows
+    // este no tiene consola y no podría leer ni escribir por la entrada y salida estándar.
+    let args: Vec<String> = std::env::args().collect();
+    let start_board = match args.iter().position(|a| a == "--fen") {
+        Some(i) => {
+            let fen = args.get(i + 1).map(String::as_str).unwrap_or("");
+            match Board::from_fen(fen) {
+                Ok(board) => board,
+                Err(e) => {
+                    eprintln!("FEN inválida: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => Board::new(),
+    };
+
     let resources_dir = if let Ok(manifest_dir) = std::env::var("CARGO_MANIFEST_DIR") {
         let mut path = PathBuf::from(manifest_dir);
         path.push("assets");
@@ -23,6 +43,13 @@ fn main() {
         PathBuf::from("./assets")
     };
 
+    let options = GameOptions {
+        start_board,
+        seventy_five_move_rule: args.iter().any(|a| a == "--regla-75"),
+        book: load_book(&args, &resources_dir).map(Arc::new),
+        tablebases: load_tablebases(&args).map(Arc::new),
+    };
+
     let (mut ctx, event_loop) = ContextBuilder::new("chess", "thedharex")
         .window_setup(conf::WindowSetup::default().title("Ajedrez - Rust"))
         .window_mode(conf::WindowMode::default()
@@ -35,12 +62,54 @@ fn main() {
 
     ctx.gfx.window().set_resizable(false);
     ctx.gfx.window().set_maximized(false);
-    let _ = ctx.gfx.window().set_inner_size(ggez::winit::dpi::PhysicalSize::new(SCREEN_SIZE.0, SCREEN_SIZE.1));
-
-    let _args: Vec<String> = std::env::args().collect();
-    // Tal vez manejar argumentos después
+    ctx.gfx.window().set_inner_size(ggez::winit::dpi::PhysicalSize::new(SCREEN_SIZE.0, SCREEN_SIZE.1));
 
-    let state = GameState::new(&mut ctx).expect("No se pudo crear el estado del juego");
+    let state = GameState::new(&mut ctx, options).expect("No se pudo crear el estado del juego");
 
     event::run(ctx, event_loop, state);
 }
+
+// Libro de aperturas según las opciones. Sin --libro se usa el que acompaña al juego, si está.
+fn load_book(args: &[String], resources_dir: &Path) -> Option<OpeningBook> {
+    if args.iter().any(|a| a == "--sin-libro") {
+        return None;
+    }
+    let value = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+
+    let explicit = value("--libro").map(PathBuf::from);
+    let path = explicit.clone().unwrap_or_else(|| resources_dir.join("book.bin"));
+    let mut book = match OpeningBook::load(&path) {
+        Ok(book) => book,
+        Err(e) if explicit.is_some() => {
+            eprintln!("No se pudo leer el libro de aperturas {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+        Err(_) => return None,
+    };
+
+    if let Some(depth) = value("--libro-jugadas") {
+        match depth.parse() {
+            Ok(depth) => book.depth = depth,
+            Err(_) => {
+                eprintln!("Número de jugadas del libro inválido: {}", depth);
+                std::process::exit(1);
+            }
+        }
+    }
+    if args.iter().any(|a| a == "--libro-mejor") {
+        book.mode = BookMode::BestMove;
+    }
+    Some(book)
+}
+
+// Tablas de finales de --syzygy. Si no hay ninguna se avisa y la IA juega sin ellas.
+fn load_tablebases(args: &[String]) -> Option<Tablebases> {
+    let paths = args.iter().position(|a| a == "--syzygy").and_then(|i| args.get(i + 1))?;
+    let tablebases = Tablebases::open(paths);
+    if tablebases.is_empty() {
+        eprintln!("No se encontraron tablas de finales Syzygy en {}", paths);
+        return None;
+    }
+    println!("Tablas de finales: {} (hasta {} piezas)", tablebases.len(), tablebases.max_pieces());
+    Some(tablebases)
+}
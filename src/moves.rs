@@ -0,0 +1,53 @@
+use std::fmt;
+use serde::{Serialize, Deserialize};
+use crate::board::square_name;
+use crate::pieces::PieceType;
+
+// Piezas a las que puede coronar un peón, de mayor a menor valor
+pub const PROMOTION_PIECES: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Move {
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    pub promotion: Option<PieceType>, // Pieza elegida al coronar
+    pub is_capture: bool,
+    pub is_castle: bool,
+    pub is_en_passant: bool,
+}
+
+impl Move {
+    pub fn new(from: (usize, usize), to: (usize, usize)) -> Self {
+        Self {
+            from,
+            to,
+            promotion: None,
+            is_capture: false,
+            is_castle: false,
+            is_en_passant: false,
+        }
+    }
+}
+
+// Notación de coordenadas: origen, destino y pieza de coronación ("e2e4", "e7e8q")
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", square_name(self.from), square_name(self.to))?;
+        if let Some(promotion) = self.promotion {
+            let c = match promotion {
+                PieceType::Queen => 'q',
+                PieceType::Rook => 'r',
+                PieceType::Bishop => 'b',
+                PieceType::Knight => 'n',
+                PieceType::Pawn | PieceType::King => '?',
+            };
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
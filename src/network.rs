@@ -3,17 +3,25 @@ use std::net::TcpStream;
 use std::io::{Read, Write};
 use std::thread;
 use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use crate::pieces::PieceColor;
+use crate::moves::Move;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum NetworkMessage {
-    Handshake { color: PieceColor },
-    Move { from: (usize, usize), to: (usize, usize) },
+    // Color del anfitrión (el elegido, o el sorteado si eligió al azar); el invitado lleva el otro
+    Handshake { host_color: PieceColor },
+    Move(Move),
+    Resign,
+    // Tablas reclamadas por la regla de los 50 movimientos (con `--regla-75`), en el turno de quien reclama
+    ClaimFiftyMoveDraw,
 }
 
 pub struct NetworkClient {
     stream: TcpStream,
     rx: mpsc::Receiver<NetworkMessage>,
+    connected: Arc<AtomicBool>,
 }
 
 impl NetworkClient {
@@ -21,6 +29,8 @@ impl NetworkClient {
         stream.set_nonblocking(false).ok();  
         let mut stream_clone = stream.try_clone().unwrap();
         let (tx, rx) = mpsc::channel();
+        let connected = Arc::new(AtomicBool::new(true));
+        let connected_flag = connected.clone();
 
         thread::spawn(move || {
             let mut buffer = [0u8; 1024];
@@ -38,11 +48,13 @@ impl NetworkClient {
                     }
                 }
             }
+            connected_flag.store(false, Ordering::SeqCst);
         });
 
         Self {
             stream,
             rx,
+            connected,
         }
     }
 
@@ -55,4 +67,9 @@ impl NetworkClient {
     pub fn try_recv(&self) -> Option<NetworkMessage> {
         self.rx.try_recv().ok()
     }
+
+    // Falso en cuanto el rival cierra la conexión o esta se corta
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
 }
@@ -0,0 +1,159 @@
+use std::time::Instant;
+use crate::board::Board;
+use crate::moves::Move;
+
+impl Board {
+    // Número de posiciones hoja del árbol de jugadas legales a `depth` medios movimientos.
+    // Sirve para comparar la generación de jugadas con recuentos conocidos.
+    pub fn perft(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.generate_legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+
+        let mut nodes = 0;
+        for mv in moves {
+            let undo = self.make_move(mv);
+            nodes += self.perft(depth - 1);
+            self.unmake_move(undo);
+        }
+        nodes
+    }
+
+    // Perft desglosado por la primera jugada, para localizar en qué rama falla un recuento
+    pub fn divide(&mut self, depth: u32) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+        self.generate_legal_moves()
+            .into_iter()
+            .map(|mv| {
+                let undo = self.make_move(mv);
+                let nodes = self.perft(depth - 1);
+                self.unmake_move(undo);
+                (mv, nodes)
+            })
+            .collect()
+    }
+}
+
+// Subcomando `chess-uci perft <profundidad> ["<FEN>"]`
+pub fn run_command(args: &[String]) {
+    let depth = match args.first().map(|d| d.parse::<u32>()) {
+        Some(Ok(depth)) if depth > 0 => depth,
+        _ => {
+            eprintln!("Uso: chess-uci perft <profundidad> [\"<FEN>\"]");
+            std::process::exit(1);
+        }
+    };
+    let mut board = match args.get(1) {
+        Some(fen) => match Board::from_fen(fen) {
+            Ok(board) => board,
+            Err(e) => {
+                eprintln!("FEN inválida: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => Board::new(),
+    };
+
+    let start = Instant::now();
+    let mut divide = board.divide(depth);
+    divide.sort_by_key(|(mv, _)| mv.to_string());
+    for (mv, nodes) in &divide {
+        println!("{}: {}", mv, nodes);
+    }
+    let total: u64 = divide.iter().map(|(_, nodes)| nodes).sum();
+    let elapsed = start.elapsed().as_secs_f64();
+
+    println!();
+    println!("Nodos: {}", total);
+    println!("Tiempo: {:.3} s ({:.0} nodos/s)", elapsed, total as f64 / elapsed.max(1e-9));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::START_FEN;
+
+    // Posiciones de referencia habituales con sus recuentos conocidos
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    const POSITION_4_MIRRORED: &str = "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+    const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    fn assert_perft(fen: &str, expected: &[u64]) {
+        let mut board = Board::from_fen(fen).unwrap();
+        for (depth, &nodes) in expected.iter().enumerate() {
+            assert_eq!(board.perft(depth as u32 + 1), nodes, "perft({}) de {}", depth + 1, fen);
+        }
+        // Hacer y deshacer todas las jugadas debe dejar el tablero intacto
+        assert_eq!(board.to_fen(), Board::from_fen(fen).unwrap().to_fen());
+    }
+
+    #[test]
+    fn start_position() {
+        assert_perft(START_FEN, &[20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    fn kiwipete() {
+        assert_perft(KIWIPETE, &[48, 2039, 97862]);
+    }
+
+    #[test]
+    fn position_3() {
+        assert_perft(POSITION_3, &[14, 191, 2812, 43238]);
+    }
+
+    #[test]
+    fn position_4() {
+        assert_perft(POSITION_4, &[6, 264, 9467]);
+        assert_perft(POSITION_4_MIRRORED, &[6, 264, 9467]);
+    }
+
+    #[test]
+    fn position_5() {
+        assert_perft(POSITION_5, &[44, 1486, 62379]);
+    }
+
+    #[test]
+    fn position_6() {
+        assert_perft(POSITION_6, &[46, 2079, 89890]);
+    }
+
+    #[test]
+    fn divide_adds_up_to_perft() {
+        let mut board = Board::from_fen(KIWIPETE).unwrap();
+        let divide = board.divide(2);
+        assert_eq!(divide.len(), 48);
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), board.perft(2));
+    }
+
+    // Casos aislados de enroque y captura al paso que suelen fallar
+    #[test]
+    fn castling_and_en_passant_edge_cases() {
+        let cases = [
+            // Enroques con las Torres en sus esquinas, incluidas capturas de Torre que quitan el derecho
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", 1, 26),
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", 3, 13744),
+            ("4k3/8/8/8/8/8/8/4K2R w K - 0 1", 4, 7059),
+            // Captura al paso que dejaría al Rey en jaque por la fila
+            ("8/8/8/KPp4r/8/8/8/7k w - c6 0 2", 1, 4),
+            ("3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1", 6, 1134888),
+            // Captura al paso que da jaque descubierto
+            ("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", 6, 1440467),
+            // Coronaciones con captura
+            ("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1", 3, 9483),
+        ];
+        for (fen, depth, nodes) in cases {
+            let mut board = Board::from_fen(fen).unwrap();
+            assert_eq!(board.perft(depth), nodes, "perft({}) de {}", depth, fen);
+        }
+    }
+}
@@ -0,0 +1,501 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::board::{Board, FenError, START_FEN};
+use crate::moves::Move;
+use crate::pieces::PieceColor;
+use crate::result::{EndReason, GameResult};
+use crate::san::{self, SanError};
+
+// Carpeta donde se guardan automáticamente las partidas terminadas
+pub const GAMES_DIR: &str = "games";
+
+// Una partida completa: etiquetas, posición de partida, jugadas y resultado
+#[derive(Clone, Debug)]
+pub struct GameRecord {
+    pub tags: Vec<(String, String)>, // En el orden en que se escriben
+    pub start: Board,
+    pub moves: Vec<Move>,
+    pub result: Option<GameResult>, // None mientras la partida sigue en curso
+}
+
+impl GameRecord {
+    // Partida vacía con las siete etiquetas obligatorias (Seven Tag Roster) por defecto
+    pub fn new(start: Board) -> Self {
+        let tags = [
+            ("Event", "Partida casual"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "-"),
+            ("White", "?"),
+            ("Black", "?"),
+            ("Result", "*"),
+        ];
+        Self {
+            tags: tags.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            start,
+            moves: Vec::new(),
+            result: None,
+        }
+    }
+
+    // Cambia el valor de una etiqueta o la añade al final si no existe
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+
+        // La etiqueta Result siempre refleja el resultado guardado
+        for (name, value) in &self.tags {
+            let value = if name == "Result" { result_token(self.result) } else { value.as_str() };
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+        }
+        // Las partidas que no empiezan en la posición inicial llevan su FEN
+        let start_fen = self.start.to_fen();
+        if start_fen != START_FEN && !self.tags.iter().any(|(name, _)| name == "FEN") {
+            pgn.push_str("[SetUp \"1\"]\n");
+            pgn.push_str(&format!("[FEN \"{}\"]\n", start_fen));
+        }
+        pgn.push('\n');
+
+        // Jugadas en SAN, con el número de movimiento antes de cada jugada blanca
+        let mut tokens = Vec::new();
+        let mut board = self.start.clone();
+        for (i, &mv) in self.moves.iter().enumerate() {
+            if board.side_to_move == PieceColor::White {
+                tokens.push(format!("{}.", board.fullmove_number));
+            } else if i == 0 {
+                tokens.push(format!("{}...", board.fullmove_number));
+            }
+            tokens.push(san::to_san(&mut board, mv));
+            board.make_move(mv);
+        }
+        tokens.push(result_token(self.result).to_string());
+
+        // Líneas de 80 caracteres como máximo
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > 80 {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        pgn
+    }
+
+    // Guarda la partida en un archivo nuevo dentro de `dir` y devuelve su ruta
+    pub fn save_to_dir(&self, dir: &Path) -> io::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let (date, time) = now_utc();
+        let base = format!("partida-{}-{}", date.replace('.', ""), time.replace(':', ""));
+        let mut path = dir.join(format!("{}.pgn", base));
+        let mut n = 2;
+        while path.exists() {
+            path = dir.join(format!("{}-{}.pgn", base, n));
+            n += 1;
+        }
+        fs::write(&path, self.to_pgn())?;
+        Ok(path)
+    }
+}
+
+// "1-0", "0-1", "1/2-1/2" o "*" si la partida no ha terminado
+pub fn result_token(result: Option<GameResult>) -> &'static str {
+    match result {
+        Some(GameResult::WhiteWins(_)) => "1-0",
+        Some(GameResult::BlackWins(_)) => "0-1",
+        Some(GameResult::Draw(_)) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+// Fecha de hoy en el formato de la etiqueta Date ("2024.05.17")
+pub fn today() -> String {
+    now_utc().0
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Fecha ("AAAA.MM.DD") y hora ("HH:MM:SS") actuales en UTC
+fn now_utc() -> (String, String) {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Conversión de días desde 1970-01-01 a fecha civil (algoritmo de Howard Hinnant)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (
+        format!("{:04}.{:02}.{:02}", year, month, day),
+        format!("{:02}:{:02}:{:02}", secs_of_day / 3_600, secs_of_day / 60 % 60, secs_of_day % 60),
+    )
+}
+
+// Errores al leer un archivo PGN; `game` empieza en 1 y `ply` cuenta medios movimientos desde el inicio
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgnError {
+    Syntax { game: usize, message: String },
+    InvalidFen { game: usize, error: FenError },
+    IllegalMove { game: usize, ply: usize, error: SanError },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnError::Syntax { game, message } => write!(f, "Partida {}: {}", game, message),
+            PgnError::InvalidFen { game, error } => write!(f, "Partida {}: {}", game, error),
+            PgnError::IllegalMove { game, ply, error } => {
+                write!(f, "Partida {}, medio movimiento {}: {}", game, ply, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Move(String),
+    Result(String),
+    VariationStart,
+    VariationEnd,
+}
+
+// Lee todas las partidas de un texto PGN. Los comentarios, NAG y números de jugada se ignoran;
+// las variantes se comprueban igual que la línea principal pero no se guardan.
+pub fn parse_pgn(text: &str) -> Result<Vec<GameRecord>, PgnError> {
+    let tokens = tokenize(text)?;
+    let mut games = Vec::new();
+    let mut pos = 0;
+
+    while pos < tokens.len() {
+        let game = games.len() + 1;
+
+        // Sección de etiquetas
+        let mut tags = Vec::new();
+        while let Some(Token::Tag(name, value)) = tokens.get(pos) {
+            tags.push((name.clone(), value.clone()));
+            pos += 1;
+        }
+
+        // Texto de jugadas, hasta el resultado o la siguiente etiqueta
+        let start = pos;
+        while pos < tokens.len() && !matches!(tokens[pos], Token::Tag(..) | Token::Result(_)) {
+            pos += 1;
+        }
+        let movetext = &tokens[start..pos];
+        let result_token = match tokens.get(pos) {
+            Some(Token::Result(result)) => {
+                pos += 1;
+                Some(result.as_str())
+            }
+            _ => None,
+        };
+
+        let fen = tags.iter().find(|(name, _)| name == "FEN").map(|(_, value)| value.as_str());
+        let start_board = match fen {
+            Some(fen) => Board::from_fen(fen).map_err(|error| PgnError::InvalidFen { game, error })?,
+            None => Board::new(),
+        };
+
+        let mut record = GameRecord::new(start_board.clone());
+        for (name, value) in &tags {
+            record.set_tag(name, value);
+        }
+        let mut line_pos = 0;
+        let mut board = read_line(movetext, &mut line_pos, start_board, 0, game, false, &mut record.moves)?;
+
+        let result = result_token
+            .or_else(|| tags.iter().find(|(name, _)| name == "Result").map(|(_, value)| value.as_str()))
+            .unwrap_or("*");
+        record.result = parse_result(result, &mut board);
+        games.push(record);
+    }
+
+    Ok(games)
+}
+
+// Recorre una línea de jugadas comprobando cada una con el generador de jugadas del tablero.
+// Una variante sustituye a la última jugada, así que se juega desde la posición anterior a ella.
+fn read_line(
+    tokens: &[Token],
+    pos: &mut usize,
+    mut board: Board,
+    mut ply: usize,
+    game: usize,
+    is_variation: bool,
+    moves: &mut Vec<Move>,
+) -> Result<Board, PgnError> {
+    let syntax = |message: &str| PgnError::Syntax { game, message: message.to_string() };
+    let mut previous: Option<Board> = None;
+
+    while let Some(token) = tokens.get(*pos) {
+        *pos += 1;
+        match token {
+            Token::Move(text) => {
+                let mv = san::parse_san(&mut board, text)
+                    .map_err(|error| PgnError::IllegalMove { game, ply: ply + 1, error })?;
+                previous = Some(board.clone());
+                board.make_move(mv);
+                moves.push(mv);
+                ply += 1;
+            }
+            Token::VariationStart => {
+                let before = previous.clone().ok_or_else(|| syntax("variante antes de la primera jugada"))?;
+                read_line(tokens, pos, before, ply - 1, game, true, &mut Vec::new())?;
+            }
+            Token::VariationEnd if is_variation => return Ok(board),
+            Token::VariationEnd => return Err(syntax("')' sin '(' correspondiente")),
+            Token::Tag(..) | Token::Result(_) => return Err(syntax("etiqueta o resultado dentro de las jugadas")),
+        }
+    }
+
+    if is_variation {
+        return Err(syntax("variante sin cerrar"));
+    }
+    Ok(board)
+}
+
+// El motivo no viene en el PGN: se deduce de la posición final si es posible
+fn parse_result(token: &str, board: &mut Board) -> Option<GameResult> {
+    let outcome = board.outcome();
+    match token {
+        "1-0" => match outcome {
+            Some(result @ GameResult::WhiteWins(_)) => Some(result),
+            _ => Some(GameResult::WhiteWins(EndReason::Resignation)),
+        },
+        "0-1" => match outcome {
+            Some(result @ GameResult::BlackWins(_)) => Some(result),
+            _ => Some(GameResult::BlackWins(EndReason::Resignation)),
+        },
+        "1/2-1/2" => match outcome {
+            Some(result @ GameResult::Draw(_)) => Some(result),
+            _ => Some(GameResult::Draw(EndReason::Agreement)),
+        },
+        _ => None,
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut at_line_start = true;
+    // Número de la partida que se está leyendo, para los mensajes de error
+    let game = |tokens: &Vec<Token>| tokens.iter().filter(|t| matches!(t, Token::Result(_))).count() + 1;
+    let syntax = |tokens: &Vec<Token>, message: &str| PgnError::Syntax { game: game(tokens), message: message.to_string() };
+
+    while let Some(c) = chars.next() {
+        let line_start = at_line_start;
+        at_line_start = c == '\n';
+        match c {
+            _ if c.is_whitespace() => {}
+            // Línea de escape y comentario hasta el final de la línea
+            '%' if line_start => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        at_line_start = true;
+                        break;
+                    }
+                }
+            }
+            ';' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        at_line_start = true;
+                        break;
+                    }
+                }
+            }
+            '{' => {
+                if !chars.by_ref().any(|c| c == '}') {
+                    return Err(syntax(&tokens, "comentario sin cerrar"));
+                }
+            }
+            '[' => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() && (c.is_alphanumeric() || c == '_') {
+                    name.push(c);
+                    chars.next();
+                }
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                if name.is_empty() || chars.next() != Some('"') {
+                    return Err(syntax(&tokens, "etiqueta mal formada"));
+                }
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => value.extend(chars.next()),
+                        Some('"') => break,
+                        Some(c) => value.push(c),
+                        None => return Err(syntax(&tokens, "etiqueta sin cerrar")),
+                    }
+                }
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                if chars.next() != Some(']') {
+                    return Err(syntax(&tokens, "etiqueta mal formada"));
+                }
+                tokens.push(Token::Tag(name, value));
+            }
+            '(' => tokens.push(Token::VariationStart),
+            ')' => tokens.push(Token::VariationEnd),
+            '*' => tokens.push(Token::Result("*".to_string())),
+            // NAG: "$1", "$14"...
+            '$' => while chars.next_if(|c| c.is_ascii_digit()).is_some() {},
+            _ => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() && !c.is_whitespace() && !"{}()[];$".contains(c) {
+                    word.push(c);
+                    chars.next();
+                }
+                match word.as_str() {
+                    "1-0" | "0-1" | "1/2-1/2" => tokens.push(Token::Result(word)),
+                    // Sufijo opcional de la captura al paso escrito aparte ("exd6 e.p.")
+                    "e.p." => {}
+                    // Número de jugada ("12", "12." o "12..."), quizá pegado a la jugada ("12.e4")
+                    _ if word.starts_with(|c: char| c.is_ascii_digit()) && !word.starts_with("0-0") => {
+                        let rest = word.trim_start_matches(|c: char| c.is_ascii_digit()).trim_start_matches('.');
+                        if !rest.is_empty() {
+                            tokens.push(Token::Move(rest.to_string()));
+                        }
+                    }
+                    _ => tokens.push(Token::Move(word)),
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves(record: &GameRecord) -> Vec<String> {
+        record.moves.iter().map(Move::to_string).collect()
+    }
+
+    #[test]
+    fn several_games_with_comments_and_nags() {
+        let text = r#"[Event "Primera"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+1. e4 {apertura del peón de rey} e5 ; comentario hasta el final de la línea
+2. Nf3 $1 Nc6 $14 3. Bb5 1-0
+
+[Event "Segunda"]
+[Result "0-1"]
+
+1. f3 e5 2. g4?? Qh4# 0-1
+"#;
+        let games = parse_pgn(text).unwrap();
+        assert_eq!(games.len(), 2);
+
+        assert_eq!(moves(&games[0]), ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5"]);
+        assert!(games[0].tags.contains(&("White".to_string(), "A".to_string())));
+        assert_eq!(games[0].result, Some(GameResult::WhiteWins(EndReason::Resignation)));
+
+        assert_eq!(moves(&games[1]), ["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert!(games[1].tags.contains(&("Event".to_string(), "Segunda".to_string())));
+        // El motivo se deduce de la posición final
+        assert_eq!(games[1].result, Some(GameResult::BlackWins(EndReason::Checkmate)));
+    }
+
+    #[test]
+    fn nested_variations() {
+        let text = "1. e4 e5 (1... c5 2. Nf3 (2. c3 d5 (2... Nf6)) 2... d6) 2. Nf3 (2. f4 exf4) Nc6 *";
+        let games = parse_pgn(text).unwrap();
+        assert_eq!(games.len(), 1);
+        // Las variantes se comprueban pero no se guardan
+        assert_eq!(moves(&games[0]), ["e2e4", "e7e5", "g1f3", "b8c6"]);
+        assert_eq!(games[0].result, None);
+
+        // Una jugada ilegal dentro de una variante también es un error
+        let err = parse_pgn("1. e4 e5 (1... c5 2. Nf6) 2. Nf3 *").unwrap_err();
+        assert!(matches!(err, PgnError::IllegalMove { game: 1, ply: 3, .. }));
+        assert!(matches!(parse_pgn("1. e4 (1. d4 e5 *").unwrap_err(), PgnError::Syntax { .. }));
+        assert!(matches!(parse_pgn("1. e4 ) e5 *").unwrap_err(), PgnError::Syntax { .. }));
+    }
+
+    #[test]
+    fn setup_position() {
+        let text = r#"[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 b - - 0 40"]
+
+40... Kd7 41. e4 Ke6 1/2-1/2
+"#;
+        let games = parse_pgn(text).unwrap();
+        assert_eq!(games[0].start.to_fen(), "4k3/8/8/8/8/8/4P3/4K3 b - - 0 40");
+        assert_eq!(moves(&games[0]), ["e8d7", "e2e4", "d7e6"]);
+        assert_eq!(games[0].result, Some(GameResult::Draw(EndReason::Agreement)));
+
+        let err = parse_pgn("[FEN \"4k3/8/8/8/8/8/8/4K3 x - - 0 1\"]\n\n*").unwrap_err();
+        assert!(matches!(err, PgnError::InvalidFen { game: 1, .. }));
+    }
+
+    #[test]
+    fn illegal_move_position() {
+        // La tercera jugada de la segunda partida (2. Ke3) no es legal
+        let text = "1. e4 e5 1-0\n\n1. e4 e5 2. Ke3 Nc6 0-1\n";
+        let err = parse_pgn(text).unwrap_err();
+        assert_eq!(err, PgnError::IllegalMove {
+            game: 2,
+            ply: 3,
+            error: SanError::Illegal("Ke3".to_string()),
+        });
+        assert_eq!(err.to_string(), "Partida 2, medio movimiento 3: La jugada 'Ke3' no es legal en esta posición");
+    }
+
+    #[test]
+    fn export_round_trip() {
+        // Partida con enroque, captura al paso y coronación desde una posición propia
+        let start = Board::from_fen("r3k2r/6P1/8/8/3p4/8/4P3/R3K2R w KQkq - 0 30").unwrap();
+        let mut record = GameRecord::new(start.clone());
+        record.set_tag("White", "Blancas \"B\"");
+        let mut board = start;
+        for text in ["e2e4", "d4e3", "e1g1", "e8c8", "g7g8q"] {
+            let mv = board.generate_legal_moves().into_iter().find(|mv| mv.to_string() == text).unwrap();
+            board.make_move(mv);
+            record.moves.push(mv);
+        }
+        record.result = Some(GameResult::WhiteWins(EndReason::Resignation));
+
+        let pgn = record.to_pgn();
+        assert!(pgn.contains("[SetUp \"1\"]"));
+        assert!(pgn.contains("30. e4 dxe3 31. O-O O-O-O 32. g8=Q 1-0"));
+
+        let games = parse_pgn(&pgn).unwrap();
+        assert_eq!(games.len(), 1);
+        let read = &games[0];
+        assert_eq!(read.start.to_fen(), record.start.to_fen());
+        assert_eq!(read.moves, record.moves);
+        assert_eq!(read.result, record.result);
+        assert!(read.tags.contains(&("White".to_string(), "Blancas \"B\"".to_string())));
+        assert_eq!(read.to_pgn(), pgn);
+    }
+}
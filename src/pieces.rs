@@ -29,7 +29,6 @@ pub enum PieceType {
 pub struct Piece {
     pub piece_type: PieceType,
     pub color: PieceColor,
-    pub has_moved: bool, // Importante para el movimiento doble del Peón y el Enroque
 }
 
 impl Piece {
@@ -37,7 +36,6 @@ impl Piece {
         Self {
             piece_type,
             color,
-            has_moved: false,
         }
     }
 }
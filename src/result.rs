@@ -0,0 +1,62 @@
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use crate::pieces::PieceColor;
+
+// Motivo por el que terminó la partida
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EndReason {
+    Checkmate,
+    Stalemate,
+    ThreefoldRepetition,
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+    InsufficientMaterial,
+    Agreement,
+    Resignation,
+    Timeout,
+    Disconnect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameResult {
+    WhiteWins(EndReason),
+    BlackWins(EndReason),
+    Draw(EndReason),
+}
+
+impl GameResult {
+    pub fn win_for(color: PieceColor, reason: EndReason) -> Self {
+        match color {
+            PieceColor::White => GameResult::WhiteWins(reason),
+            PieceColor::Black => GameResult::BlackWins(reason),
+        }
+    }
+}
+
+impl fmt::Display for EndReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            EndReason::Checkmate => "jaque mate",
+            EndReason::Stalemate => "rey ahogado",
+            EndReason::ThreefoldRepetition => "triple repetición",
+            EndReason::FiftyMoveRule => "la regla de los 50 movimientos",
+            EndReason::SeventyFiveMoveRule => "la regla de los 75 movimientos",
+            EndReason::InsufficientMaterial => "material insuficiente",
+            EndReason::Agreement => "acuerdo mutuo",
+            EndReason::Resignation => "abandono",
+            EndReason::Timeout => "tiempo agotado",
+            EndReason::Disconnect => "desconexión",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameResult::WhiteWins(reason) => write!(f, "Ganan las Blancas por {}", reason),
+            GameResult::BlackWins(reason) => write!(f, "Ganan las Negras por {}", reason),
+            GameResult::Draw(reason) => write!(f, "Tablas por {}", reason),
+        }
+    }
+}
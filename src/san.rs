@@ -0,0 +1,278 @@
+use std::fmt;
+use crate::board::{Board, parse_square, square_name};
+use crate::moves::Move;
+use crate::pieces::PieceType;
+
+// Errores al interpretar una jugada en notación algebraica estándar (SAN)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SanError {
+    Invalid(String),
+    Illegal(String),
+    Ambiguous(String),
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SanError::Invalid(s) => write!(f, "Jugada '{}' mal escrita", s),
+            SanError::Illegal(s) => write!(f, "La jugada '{}' no es legal en esta posición", s),
+            SanError::Ambiguous(s) => write!(f, "La jugada '{}' es ambigua", s),
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+// SAN usa las iniciales inglesas de las piezas, como PGN
+fn piece_letter(piece_type: PieceType) -> Option<char> {
+    match piece_type {
+        PieceType::Pawn => None,
+        PieceType::Knight => Some('N'),
+        PieceType::Bishop => Some('B'),
+        PieceType::Rook => Some('R'),
+        PieceType::Queen => Some('Q'),
+        PieceType::King => Some('K'),
+    }
+}
+
+fn piece_from_letter(c: char) -> Option<PieceType> {
+    match c {
+        'N' => Some(PieceType::Knight),
+        'B' => Some(PieceType::Bishop),
+        'R' => Some(PieceType::Rook),
+        'Q' => Some(PieceType::Queen),
+        'K' => Some(PieceType::King),
+        _ => None,
+    }
+}
+
+// Escribe una jugada legal de la posición actual en SAN ("Nbd7", "exd6", "O-O-O", "e8=N+", "Qh4#").
+// El tablero se usa para probar la jugada, pero queda como estaba.
+pub fn to_san(board: &mut Board, mv: Move) -> String {
+    let Some(piece) = board.piece_at(mv.from) else {
+        return mv.to_string();
+    };
+
+    let mut san = String::new();
+    if piece.piece_type == PieceType::King && mv.from.0.abs_diff(mv.to.0) == 2 {
+        san.push_str(if mv.to.0 > mv.from.0 { "O-O" } else { "O-O-O" });
+    } else {
+        let is_capture = board.piece_at(mv.to).is_some() || mv.is_en_passant;
+        match piece_letter(piece.piece_type) {
+            Some(letter) => {
+                san.push(letter);
+
+                // Otras piezas iguales que también pueden ir al mismo destino
+                let others: Vec<Move> = board.generate_legal_moves()
+                    .into_iter()
+                    .filter(|other| {
+                        other.to == mv.to
+                            && other.from != mv.from
+                            && board.piece_at(other.from).map(|p| p.piece_type) == Some(piece.piece_type)
+                    })
+                    .collect();
+                if !others.is_empty() {
+                    // Basta la columna si la distingue; si no, la fila; si tampoco, ambas
+                    let from = square_name(mv.from);
+                    if others.iter().all(|other| other.from.0 != mv.from.0) {
+                        san.push_str(&from[..1]);
+                    } else if others.iter().all(|other| other.from.1 != mv.from.1) {
+                        san.push_str(&from[1..]);
+                    } else {
+                        san.push_str(&from);
+                    }
+                }
+            }
+            // Las capturas de peón llevan la columna de origen
+            None if is_capture => san.push_str(&square_name(mv.from)[..1]),
+            None => {}
+        }
+        if is_capture {
+            san.push('x');
+        }
+        san.push_str(&square_name(mv.to));
+        if let Some(letter) = mv.promotion.and_then(piece_letter) {
+            san.push('=');
+            san.push(letter);
+        }
+    }
+
+    // Jaque o jaque mate
+    let undo = board.make_move(mv);
+    if board.is_in_check(board.side_to_move) {
+        san.push(if board.generate_legal_moves().is_empty() { '#' } else { '+' });
+    }
+    board.unmake_move(undo);
+
+    san
+}
+
+// Busca la jugada legal que corresponde a un texto SAN. Acepta "0-0" por "O-O", la coronación
+// sin '=' ("e8Q"), el sufijo "e.p." y anotaciones como "+", "#", "!" o "?".
+pub fn parse_san(board: &mut Board, text: &str) -> Result<Move, SanError> {
+    let invalid = || SanError::Invalid(text.to_string());
+    let illegal = || SanError::Illegal(text.to_string());
+
+    let trimmed = text.trim();
+    let san = trimmed.strip_suffix("e.p.").unwrap_or(trimmed).trim_end();
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    if san.is_empty() {
+        return Err(invalid());
+    }
+
+    let legal = board.generate_legal_moves();
+
+    // Enroques
+    if let Some(kingside) = match san {
+        "O-O" | "0-0" => Some(true),
+        "O-O-O" | "0-0-0" => Some(false),
+        _ => None,
+    } {
+        return legal.into_iter()
+            .find(|mv| mv.is_castle && (mv.to.0 > mv.from.0) == kingside)
+            .ok_or_else(illegal);
+    }
+
+    let mut chars: Vec<char> = san.chars().collect();
+    let piece_type = match piece_from_letter(chars[0]) {
+        Some(piece_type) => {
+            chars.remove(0);
+            piece_type
+        }
+        None => PieceType::Pawn,
+    };
+
+    // Pieza de coronación al final ("e8=Q" o "e8Q")
+    let mut promotion = None;
+    if piece_type == PieceType::Pawn
+        && let Some(promoted) = chars.last().copied().and_then(piece_from_letter) {
+        if promoted == PieceType::King {
+            return Err(invalid());
+        }
+        promotion = Some(promoted);
+        chars.pop();
+        if chars.last() == Some(&'=') {
+            chars.pop();
+        }
+    }
+
+    // Casilla de destino
+    if chars.len() < 2 {
+        return Err(invalid());
+    }
+    let dest: String = chars.split_off(chars.len() - 2).into_iter().collect();
+    let to = parse_square(&dest).ok_or_else(invalid)?;
+
+    let is_capture = chars.last() == Some(&'x');
+    if is_capture {
+        chars.pop();
+    }
+
+    // Lo que queda es la desambiguación: columna, fila o ambas
+    let (mut from_file, mut from_rank) = (None, None);
+    for c in chars {
+        match c {
+            'a'..='h' if from_file.is_none() && from_rank.is_none() => from_file = Some(c as usize - 'a' as usize),
+            '1'..='8' if from_rank.is_none() => from_rank = Some(8 - (c as usize - '0' as usize)),
+            _ => return Err(invalid()),
+        }
+    }
+    // Un peón solo indica su columna, y únicamente al capturar
+    if piece_type == PieceType::Pawn && (from_rank.is_some() || from_file.is_some() != is_capture) {
+        return Err(invalid());
+    }
+
+    let candidates: Vec<Move> = legal.into_iter()
+        .filter(|mv| {
+            mv.to == to
+                && !mv.is_castle
+                && board.piece_at(mv.from).map(|p| p.piece_type) == Some(piece_type)
+                && from_file.is_none_or(|file| mv.from.0 == file)
+                && from_rank.is_none_or(|rank| mv.from.1 == rank)
+                && (!is_capture || mv.is_capture)
+                && (piece_type != PieceType::Pawn || mv.is_capture == is_capture)
+                && (promotion.is_none() || mv.promotion == promotion)
+        })
+        .collect();
+
+    match candidates.as_slice() {
+        [] => Err(illegal()),
+        [mv] => Ok(*mv),
+        // Varias piezas posibles, o una coronación sin indicar la pieza
+        _ => Err(SanError::Ambiguous(text.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // SAN de la jugada en coordenadas ("g1f3") en la posición dada
+    fn san_of(fen: &str, text: &str) -> String {
+        let mut board = Board::from_fen(fen).unwrap();
+        let mv = board.generate_legal_moves().into_iter().find(|mv| mv.to_string() == text).unwrap();
+        to_san(&mut board, mv)
+    }
+
+    fn parse(fen: &str, san: &str) -> Result<String, SanError> {
+        parse_san(&mut Board::from_fen(fen).unwrap(), san).map(|mv| mv.to_string())
+    }
+
+    #[test]
+    fn disambiguation() {
+        // Dos caballos llegan a d7: basta la columna
+        let knights = "rnbqkb1r/ppp1pppp/5n2/3p4/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1";
+        assert_eq!(san_of(knights, "b8d7"), "Nbd7");
+        assert_eq!(san_of(knights, "f6d7"), "Nfd7");
+        assert_eq!(parse(knights, "Nbd7"), Ok("b8d7".to_string()));
+        assert_eq!(parse(knights, "Nd7"), Err(SanError::Ambiguous("Nd7".to_string())));
+
+        // Dos torres en la misma columna: la fila
+        let rooks = "7k/8/8/8/8/4R3/8/K3R3 w - - 0 1";
+        assert_eq!(san_of(rooks, "e1e2"), "R1e2");
+        assert_eq!(san_of(rooks, "e3e2"), "R3e2");
+        assert_eq!(parse(rooks, "R1e2"), Ok("e1e2".to_string()));
+
+        // Tres damas: ni la columna ni la fila bastan para la de h4
+        let queens = "1k6/8/8/8/4Q2Q/8/8/K6Q w - - 0 1";
+        assert_eq!(san_of(queens, "h4e1"), "Qh4e1");
+        assert_eq!(san_of(queens, "e4e1"), "Qee1");
+        assert_eq!(san_of(queens, "h1e1"), "Q1e1");
+        assert_eq!(parse(queens, "Qh4e1"), Ok("h4e1".to_string()));
+    }
+
+    #[test]
+    fn special_moves() {
+        let en_passant = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        assert_eq!(san_of(en_passant, "e5d6"), "exd6");
+        let mut board = Board::from_fen(en_passant).unwrap();
+        let mv = parse_san(&mut board, "exd6 e.p.").unwrap();
+        assert!(mv.is_en_passant && mv.to_string() == "e5d6");
+
+        let castle = "r3k3/8/8/8/8/8/8/4K3 b q - 0 1";
+        assert_eq!(san_of(castle, "e8c8"), "O-O-O");
+        assert_eq!(parse(castle, "O-O-O"), Ok("e8c8".to_string()));
+        assert_eq!(parse(castle, "0-0-0"), Ok("e8c8".to_string()));
+
+        let promotion = "8/4P3/5k2/8/8/8/8/K7 w - - 0 1";
+        assert_eq!(san_of(promotion, "e7e8n"), "e8=N+");
+        assert_eq!(san_of(promotion, "e7e8q"), "e8=Q");
+        assert_eq!(parse(promotion, "e8=N+"), Ok("e7e8n".to_string()));
+        assert_eq!(parse(promotion, "e8N"), Ok("e7e8n".to_string()));
+
+        // Mate del loco
+        let fools_mate = "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq g3 0 2";
+        assert_eq!(san_of(fools_mate, "d8h4"), "Qh4#");
+        assert_eq!(parse(fools_mate, "Qh4#"), Ok("d8h4".to_string()));
+    }
+
+    #[test]
+    fn rejected_moves() {
+        let fen = "4k3/8/8/8/8/r7/4K3/8 w - - 0 1";
+        assert_eq!(parse(fen, "Ke3"), Err(SanError::Illegal("Ke3".to_string())));
+        assert_eq!(parse(fen, "Kd2"), Ok("e2d2".to_string()));
+        assert_eq!(parse(fen, "Zd2"), Err(SanError::Invalid("Zd2".to_string())));
+        assert_eq!(parse(fen, "K"), Err(SanError::Invalid("K".to_string())));
+        assert_eq!(parse(Board::new().to_fen().as_str(), "e5"), Err(SanError::Illegal("e5".to_string())));
+    }
+}
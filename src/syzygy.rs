@@ -0,0 +1,1344 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, OnceLock};
+use crate::board::Board;
+use crate::moves::Move;
+use crate::pieces::{PieceColor, PieceType};
+
+// Lectura de las tablas de finales Syzygy (.rtbw con el resultado WDL y .rtbz con la distancia
+// DTZ al siguiente movimiento que reinicia la regla de los 50). El formato y la forma de indexar
+// las posiciones son los del generador de Ronald de Man; este módulo sigue su sondeo paso a paso.
+
+// Más piezas de las que cubren las tablas existentes (7 con los reyes)
+const MAX_PIECES: usize = 7;
+// Rango de las jugadas de la raíz que ganan o pierden con seguridad
+const MAX_DTZ: i32 = 1 << 18;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+// Banderas de cada tabla del archivo
+const FLAG_STM: u8 = 1; // DTZ: la tabla es la del bando negro
+const FLAG_MAPPED: u8 = 2; // DTZ: los valores pasan por un mapa
+const FLAG_WIN_PLIES: u8 = 4; // DTZ: las victorias están en medios movimientos, no en jugadas
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16; // DTZ: el mapa es de 16 bits
+const FLAG_SINGLE_VALUE: u8 = 128; // Todas las posiciones tienen el mismo valor
+
+// Resultado de una posición para el bando que mueve, teniendo en cuenta la regla de los 50
+// movimientos: "Cursed" gana pero no a tiempo y "Blessed" pierde pero se salva por ella
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            ..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    fn negate(self) -> Wdl {
+        Wdl::from_value(-(self as i32))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+// Un sondeo que no llega a dar valor
+enum ProbeError {
+    Missing,          // Falta la tabla
+    Corrupt(PathBuf), // El archivo existe pero no se puede leer como tabla
+    ChangeStm,        // La tabla DTZ solo guarda el otro bando: hay que mirar un medio movimiento más
+}
+
+// Archivo de tablas dañado. Cada sondeo que lo necesita lo devuelve en lugar de un valor, para
+// que quien sondea pueda avisar; la búsqueda sigue como si no hubiera tabla.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptTable(pub PathBuf);
+
+impl fmt::Display for CorruptTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Tabla de finales dañada: {}", self.0.display())
+    }
+}
+
+// Resultado público de un sondeo: None si la posición no está en las tablas
+fn found<T>(result: Result<T, ProbeError>) -> Result<Option<T>, CorruptTable> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ProbeError::Corrupt(path)) => Err(CorruptTable(path)),
+        Err(ProbeError::Missing | ProbeError::ChangeStm) => Ok(None),
+    }
+}
+
+// Tablas de finales encontradas en los directorios configurados. Los archivos se leen la primera
+// vez que se necesitan; sin archivos no se sondea nada y el motor juega como siempre.
+pub struct Tablebases {
+    tables: HashMap<String, TableEntry>, // Por material, como el nombre del archivo ("KRvK")
+    max_pieces: usize,
+}
+
+impl Tablebases {
+    // `paths` son uno o varios directorios separados como en PATH (':' o ';' en Windows).
+    // Los que no existen se ignoran.
+    pub fn open(paths: &str) -> Self {
+        let mut tables = HashMap::new();
+        let mut max_pieces = 0;
+        for dir in env::split_paths(paths) {
+            let Ok(files) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for file in files.flatten() {
+                let path = file.path();
+                if path.extension().is_none_or(|ext| ext != "rtbw") {
+                    continue;
+                }
+                let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                    continue;
+                };
+                if tables.contains_key(name) {
+                    continue;
+                }
+                if let Some(entry) = TableEntry::new(name, &path) {
+                    max_pieces = max_pieces.max(entry.piece_count);
+                    tables.insert(name.to_string(), entry);
+                }
+            }
+        }
+        Self { tables, max_pieces }
+    }
+
+    // Sin tablas: no se sondea nada
+    pub fn empty() -> Self {
+        Self { tables: HashMap::new(), max_pieces: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    // Número de piezas (reyes incluidos) de la tabla más grande
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    // Si la posición puede estar en las tablas: pocas piezas y sin derechos de enroque
+    pub fn covers(&self, board: &Board) -> bool {
+        let castling = board.castling;
+        let can_castle = castling.white_kingside || castling.white_queenside
+            || castling.black_kingside || castling.black_queenside;
+        !can_castle && (board.occupied().count_ones() as usize) <= self.max_pieces
+    }
+
+    // Resultado de la posición para el bando que mueve, o None si no está en las tablas
+    pub fn probe_wdl(&self, board: &mut Board) -> Result<Option<Wdl>, CorruptTable> {
+        if !self.covers(board) {
+            return Ok(None);
+        }
+        found(self.search(board, false).map(|(wdl, _)| wdl))
+    }
+
+    // Distancia en medios movimientos al siguiente movimiento que reinicia la regla de los 50
+    // (captura o avance de peón) jugando lo mejor posible, o al mate. Positiva si gana el que
+    // mueve, negativa si pierde, 0 en tablas y por encima de 100 (en valor absoluto) si el
+    // resultado cambia por la regla de los 50. Puede pasarse en uno.
+    pub fn probe_dtz(&self, board: &mut Board) -> Result<Option<i32>, CorruptTable> {
+        if !self.covers(board) {
+            return Ok(None);
+        }
+        found(self.dtz(board))
+    }
+
+    // Puntúa cada jugada legal de la raíz con las tablas DTZ (mayor es mejor): ganar antes de que
+    // cuente la regla de los 50, tablas y, si se pierde, resistir lo más posible.
+    // `history` son los hashes de la partida hasta la posición actual incluida.
+    pub fn rank_root_moves(&self, board: &mut Board, history: &[u64]) -> Result<Option<Vec<(Move, i32)>>, CorruptTable> {
+        if !self.covers(board) {
+            return Ok(None);
+        }
+        let rule50 = board.halfmove_clock as i32;
+        // Una posición repetida desde la última captura o avance de peón acerca las tablas
+        let recent = &history[history.len().saturating_sub(rule50 as usize + 1)..];
+        let repeated = recent.iter().enumerate().any(|(i, hash)| recent[i + 1..].contains(hash));
+
+        let mut ranked = Vec::new();
+        let mut history = history.to_vec();
+        for mv in board.generate_legal_moves() {
+            let undo = board.make_move(mv);
+            history.push(board.hash);
+            let dtz = if board.halfmove_clock == 0 {
+                self.search(board, false).map(|(wdl, _)| dtz_before_zeroing(wdl.negate()))
+            } else if board.repetition_count(&history) >= 3 || board.is_fifty_move_draw() {
+                Ok(0)
+            } else {
+                self.dtz(board).map(|dtz| -dtz + (-dtz).signum())
+            };
+            // Una jugada que da mate vale 1
+            let dtz = dtz.map(|dtz| {
+                let mated = board.is_in_check(board.side_to_move) && board.generate_legal_moves().is_empty();
+                if dtz == 2 && mated { 1 } else { dtz }
+            });
+            history.pop();
+            board.unmake_move(undo);
+
+            let dtz = match found(dtz)? {
+                Some(dtz) => dtz,
+                None => return Ok(None),
+            };
+            let rank = match dtz.cmp(&0) {
+                Ordering::Greater if dtz + rule50 <= 99 && !repeated => MAX_DTZ - dtz,
+                Ordering::Greater => MAX_DTZ / 2 - (dtz + rule50),
+                Ordering::Less if -dtz * 2 + rule50 < 100 => -MAX_DTZ - dtz,
+                Ordering::Less => -MAX_DTZ / 2 + (-dtz + rule50),
+                Ordering::Equal => 0,
+            };
+            ranked.push((mv, rank));
+        }
+        Ok(Some(ranked))
+    }
+
+    // Resultado mirando antes las capturas (y con `check_zeroing` los avances de peón): las tablas
+    // guardan cualquier valor en las posiciones donde una de ellas es la mejor jugada.
+    // El booleano indica que la mejor jugada reinicia la regla de los 50.
+    fn search(&self, board: &mut Board, check_zeroing: bool) -> Result<(Wdl, bool), ProbeError> {
+        let moves = board.generate_legal_moves();
+        let mut best = Wdl::Loss;
+        let mut zeroing_moves = 0;
+
+        for &mv in &moves {
+            let is_pawn = board.piece_at(mv.from).is_some_and(|p| p.piece_type == PieceType::Pawn);
+            if !mv.is_capture && (!check_zeroing || !is_pawn) {
+                continue;
+            }
+            zeroing_moves += 1;
+
+            let undo = board.make_move(mv);
+            let result = self.search(board, false);
+            board.unmake_move(undo);
+            let value = result?.0.negate();
+
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Ok((value, true));
+                }
+            }
+        }
+
+        // Si ya se han mirado todas las jugadas no hace falta la tabla (que además no sabe de
+        // capturas al paso)
+        let no_more_moves = zeroing_moves > 0 && zeroing_moves == moves.len();
+        let value = if no_more_moves { best } else { self.probe_table(board, TableKind::Wdl, Wdl::Draw).map(Wdl::from_value)? };
+
+        if best >= value {
+            Ok((best, best > Wdl::Draw || no_more_moves))
+        } else {
+            Ok((value, false))
+        }
+    }
+
+    fn dtz(&self, board: &mut Board) -> Result<i32, ProbeError> {
+        let (wdl, zeroing_best) = self.search(board, true)?;
+        // Las tablas DTZ no guardan las tablas
+        if wdl == Wdl::Draw {
+            return Ok(0);
+        }
+        if zeroing_best {
+            return Ok(dtz_before_zeroing(wdl));
+        }
+
+        match self.probe_table(board, TableKind::Dtz, wdl) {
+            Ok(dtz) => {
+                let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+                Ok((dtz + if cursed { 100 } else { 0 }) * (wdl as i32).signum())
+            }
+            Err(error @ (ProbeError::Missing | ProbeError::Corrupt(_))) => Err(error),
+            // La tabla es la del otro bando: se mira cada jugada y se toma la mejor
+            Err(ProbeError::ChangeStm) => {
+                let sign = (wdl as i32).signum();
+                let mut min_dtz = i32::MAX;
+                for mv in board.generate_legal_moves() {
+                    let is_pawn = board.piece_at(mv.from).is_some_and(|p| p.piece_type == PieceType::Pawn);
+                    let zeroing = mv.is_capture || is_pawn;
+
+                    let undo = board.make_move(mv);
+                    // Tras un movimiento que reinicia la regla de los 50 interesa la distancia de
+                    // antes de hacerlo, que se deduce del resultado
+                    let result = if zeroing {
+                        self.search(board, false).map(|(wdl, _)| -dtz_before_zeroing(wdl))
+                    } else {
+                        self.dtz(board).map(|dtz| -dtz)
+                    };
+                    let mated = board.is_in_check(board.side_to_move) && board.generate_legal_moves().is_empty();
+                    board.unmake_move(undo);
+
+                    let mut dtz = result?;
+                    if dtz == 1 && mated {
+                        min_dtz = 1;
+                    }
+                    if !zeroing {
+                        dtz += dtz.signum();
+                    }
+                    if dtz < min_dtz && dtz.signum() == sign {
+                        min_dtz = dtz;
+                    }
+                }
+                // Sin jugadas legales la posición es mate
+                Ok(if min_dtz == i32::MAX { -1 } else { min_dtz })
+            }
+        }
+    }
+
+    // Valor guardado en la tabla para la posición: WDL de -2 a 2 o DTZ (con `wdl` ya conocido)
+    fn probe_table(&self, board: &Board, kind: TableKind, wdl: Wdl) -> Result<i32, ProbeError> {
+        let pieces = TbPieces::from_board(board);
+        if pieces.len() == 2 {
+            return Ok(0); // Rey contra rey
+        }
+
+        // Las tablas tienen el bando fuerte con blancas; si aquí lo lleva el negro se cambian
+        // los colores y se da la vuelta al tablero
+        let (white, black) = pieces.material();
+        let (entry, black_stronger) = match self.tables.get(&format!("{}v{}", white, black)) {
+            Some(entry) => (entry, false),
+            None => (self.tables.get(&format!("{}v{}", black, white)).ok_or(ProbeError::Missing)?, true),
+        };
+        let table = entry.table(kind)?;
+        table.probe(entry, &pieces, board.side_to_move, black_stronger, wdl)
+    }
+}
+
+// DTZ de la jugada anterior a un movimiento que reinicia la regla de los 50, según el resultado
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+        Wdl::Draw => 0,
+    }
+}
+
+// Piezas de la posición con la numeración de las tablas: casilla 0 = a1 y pieza como en el
+// archivo (1-6 peón, caballo, alfil, torre, dama y rey blancos; +8 las negras)
+struct TbPieces {
+    pieces: Vec<(usize, u8)>, // Ordenadas por casilla
+}
+
+impl TbPieces {
+    fn from_board(board: &Board) -> Self {
+        let mut pieces = Vec::new();
+        for square in 0..64 {
+            let (x, y) = (square % 8, 7 - square / 8);
+            if let Some(piece) = board.piece_at((x, y)) {
+                let code = match piece.piece_type {
+                    PieceType::Pawn => 1,
+                    PieceType::Knight => 2,
+                    PieceType::Bishop => 3,
+                    PieceType::Rook => 4,
+                    PieceType::Queen => 5,
+                    PieceType::King => 6,
+                };
+                pieces.push((square, code | if piece.color == PieceColor::Black { 8 } else { 0 }));
+            }
+        }
+        Self { pieces }
+    }
+
+    fn len(&self) -> usize {
+        self.pieces.len()
+    }
+
+    // Material de cada bando como en los nombres de archivo ("KRP", "K")
+    fn material(&self) -> (String, String) {
+        let side = |color: u8| -> String {
+            "KQRBNP".chars()
+                .zip([6, 5, 4, 3, 2, 1])
+                .flat_map(|(letter, code)| {
+                    let count = self.pieces.iter().filter(|&&(_, piece)| piece == code | color).count();
+                    std::iter::repeat_n(letter, count)
+                })
+                .collect()
+        };
+        (side(0), side(8))
+    }
+}
+
+// Una tabla encontrada (archivo .rtbw y, si está, su .rtbz), con el material que cubre.
+// El material es el del nombre, con el bando fuerte con blancas.
+struct TableEntry {
+    wdl_path: PathBuf,
+    dtz_path: PathBuf,
+    piece_count: usize,
+    has_pawns: bool,
+    has_unique_pieces: bool, // Algún bando tiene una pieza (que no sea el rey) sin repetir
+    pawn_count: [usize; 2], // Peones del bando que lleva los peones de referencia y del otro
+    symmetric: bool, // Los dos bandos tienen el mismo material ("KRvKR")
+    wdl: OnceLock<Result<Table, LoadError>>,
+    dtz: OnceLock<Result<Table, LoadError>>,
+}
+
+// Por qué no se pudo cargar una tabla: sin el archivo (normal para las DTZ) o con uno inválido
+#[derive(Clone, Copy)]
+enum LoadError {
+    Missing,
+    Corrupt,
+}
+
+impl TableEntry {
+    fn new(name: &str, wdl_path: &Path) -> Option<Self> {
+        let (white, black) = name.split_once('v')?;
+        let valid = |side: &str| side.starts_with('K') && side.chars().all(|c| "KQRBNP".contains(c));
+        if !valid(white) || !valid(black) || white.len() + black.len() > MAX_PIECES {
+            return None;
+        }
+
+        let count = |side: &str, letter: char| side.chars().filter(|&c| c == letter).count();
+        let has_unique_pieces = [white, black]
+            .iter()
+            .any(|side| "QRBNP".chars().any(|letter| count(side, letter) == 1));
+        // Llevan los peones de referencia las blancas salvo que las negras tengan menos
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let pawn_count = if white_leads { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] };
+
+        Some(Self {
+            wdl_path: wdl_path.to_path_buf(),
+            dtz_path: wdl_path.with_extension("rtbz"),
+            piece_count: white.len() + black.len(),
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces,
+            pawn_count,
+            symmetric: white == black,
+            wdl: OnceLock::new(),
+            dtz: OnceLock::new(),
+        })
+    }
+
+    // Tabla leída y preparada la primera vez. Si falta el archivo o no es válido se recuerda
+    // y no se vuelve a intentar.
+    fn table(&self, kind: TableKind) -> Result<&Table, ProbeError> {
+        let cell = match kind {
+            TableKind::Wdl => &self.wdl,
+            TableKind::Dtz => &self.dtz,
+        };
+        let loaded = cell.get_or_init(|| {
+            let bytes = fs::read(self.path(kind)).map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => LoadError::Missing,
+                _ => LoadError::Corrupt,
+            })?;
+            Table::parse(bytes, kind, self).ok_or(LoadError::Corrupt)
+        });
+        match loaded {
+            Ok(table) => Ok(table),
+            Err(LoadError::Missing) => Err(ProbeError::Missing),
+            Err(LoadError::Corrupt) => Err(ProbeError::Corrupt(self.path(kind).to_path_buf())),
+        }
+    }
+
+    fn path(&self, kind: TableKind) -> &Path {
+        match kind {
+            TableKind::Wdl => &self.wdl_path,
+            TableKind::Dtz => &self.dtz_path,
+        }
+    }
+}
+
+// Datos para descomprimir una de las tablas de un archivo: hay una por bando que mueve (solo
+// en WDL y si el material no es simétrico) y, con peones, una por columna a-d del peón de
+// referencia. Las posiciones son índices dentro de `Table::bytes`.
+#[derive(Clone, Default)]
+struct PairsData {
+    flags: u8,
+    block_size: usize,
+    span: usize, // Cada `span` valores hay una entrada en el índice disperso
+    num_blocks: usize,
+    min_sym_len: u8, // Con FLAG_SINGLE_VALUE es el valor de todas las posiciones
+    lowest_sym: usize, // Símbolo más bajo de cada longitud
+    btree: usize, // Los dos símbolos en que se expande cada símbolo, 3 bytes cada uno
+    block_lengths: usize, // Valores guardados (menos uno) en cada bloque
+    block_length_count: usize,
+    sparse_index: usize, // Entradas de 6 bytes: bloque y posición dentro de él
+    sparse_index_count: usize,
+    data: usize, // Bloques comprimidos con código de Huffman canónico
+    base64: Vec<u64>, // Símbolo más bajo de cada longitud, alineado a la izquierda en 64 bits
+    symlen: Vec<u8>, // Valores (menos uno) que representa cada símbolo
+    pieces: [u8; MAX_PIECES], // Orden de las piezas en el índice
+    group_idx: [u64; MAX_PIECES + 1], // Factor de cada grupo de piezas en el índice
+    group_len: [usize; MAX_PIECES + 1], // Piezas de cada grupo, terminado en 0
+    map_idx: [u16; 4], // DTZ: inicio del mapa de cada resultado
+}
+
+struct Table {
+    bytes: Vec<u8>,
+    kind: TableKind,
+    items: [[PairsData; 4]; 2], // [bando que mueve][columna del peón de referencia]
+    dtz_map: usize,
+}
+
+impl Table {
+    fn parse(bytes: Vec<u8>, kind: TableKind, entry: &TableEntry) -> Option<Table> {
+        let magic = if kind == TableKind::Wdl { WDL_MAGIC } else { DTZ_MAGIC };
+        if bytes.get(..4)? != magic {
+            return None;
+        }
+        let mut table = Table { bytes, kind, items: Default::default(), dtz_map: 0 };
+        table.parse_items(entry)?;
+        Some(table)
+    }
+
+    fn byte(&self, at: usize) -> Option<u8> {
+        self.bytes.get(at).copied()
+    }
+
+    fn le16(&self, at: usize) -> u16 {
+        self.bytes.get(at..at + 2).map_or(0, |b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn le32(&self, at: usize) -> u32 {
+        self.bytes.get(at..at + 4).map_or(0, |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn be32(&self, at: usize) -> u32 {
+        self.bytes.get(at..at + 4).map_or(0, |b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn be64(&self, at: usize) -> u64 {
+        (self.be32(at) as u64) << 32 | self.be32(at + 4) as u64
+    }
+
+    // Símbolos izquierdo y derecho en que se expande `sym` (12 bits cada uno)
+    fn btree(&self, d: &PairsData, sym: usize) -> (usize, usize) {
+        let at = d.btree + 3 * sym;
+        let (b0, b1, b2) = (self.byte(at).unwrap_or(0), self.byte(at + 1).unwrap_or(0), self.byte(at + 2).unwrap_or(0));
+        let left = ((b1 as usize & 0xF) << 8) | b0 as usize;
+        let right = ((b2 as usize) << 4) | (b1 as usize >> 4);
+        (left, right)
+    }
+
+    // Cabecera del archivo: piezas y orden de los grupos, tamaños, mapa DTZ, índices y datos
+    fn parse_items(&mut self, entry: &TableEntry) -> Option<()> {
+        let flags = self.byte(4)?;
+        // Bit 1: la tabla tiene peones; bit 0: el material no es simétrico
+        if (flags & 2 != 0) != entry.has_pawns || (flags & 1 != 0) == entry.symmetric {
+            return None;
+        }
+        let mut data = 5;
+        let sides = if self.kind == TableKind::Wdl && !entry.symmetric { 2 } else { 1 };
+        let files = if entry.has_pawns { 4 } else { 1 };
+        let both_have_pawns = entry.has_pawns && entry.pawn_count[1] > 0;
+
+        for file in 0..files {
+            let first = self.byte(data)?;
+            let second = if both_have_pawns { self.byte(data + 1)? } else { 0xFF };
+            let order = [[first & 0xF, second & 0xF], [first >> 4, second >> 4]];
+            data += 1 + both_have_pawns as usize;
+
+            for k in 0..entry.piece_count {
+                let byte = self.byte(data)?;
+                for side in 0..sides {
+                    self.items[side][file].pieces[k] = if side == 0 { byte & 0xF } else { byte >> 4 };
+                }
+                data += 1;
+            }
+            for (side, &order) in order.iter().enumerate().take(sides) {
+                set_groups(entry, &mut self.items[side][file], order, file);
+            }
+        }
+        data += data & 1;
+
+        for file in 0..files {
+            for side in 0..sides {
+                let mut d = std::mem::take(&mut self.items[side][file]);
+                data = self.set_sizes(&mut d, data)?;
+                self.items[side][file] = d;
+            }
+        }
+
+        if self.kind == TableKind::Dtz {
+            self.dtz_map = data;
+            for file in 0..files {
+                let flags = self.items[0][file].flags;
+                if flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                for i in 0..4 {
+                    if flags & FLAG_WIDE != 0 {
+                        data += data & 1;
+                        self.items[0][file].map_idx[i] = ((data - self.dtz_map) / 2 + 1) as u16;
+                        data += 2 * self.le16(data) as usize + 2;
+                    } else {
+                        self.items[0][file].map_idx[i] = (data - self.dtz_map + 1) as u16;
+                        data += self.byte(data)? as usize + 1;
+                    }
+                }
+            }
+            data += data & 1;
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                let d = &mut self.items[side][file];
+                d.sparse_index = data;
+                data += 6 * d.sparse_index_count;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let d = &mut self.items[side][file];
+                d.block_lengths = data;
+                data += 2 * d.block_length_count;
+            }
+        }
+        // Las tablas de un solo valor no tienen bloques, y el archivo puede acabar antes
+        let mut end = data;
+        for file in 0..files {
+            for side in 0..sides {
+                let d = &mut self.items[side][file];
+                data = (data + 0x3F) & !0x3F; // Bloques alineados a 64 bytes
+                d.data = data;
+                data += d.num_blocks * d.block_size;
+                if d.num_blocks > 0 {
+                    end = data;
+                }
+            }
+        }
+        (end <= self.bytes.len()).then_some(())
+    }
+
+    // Tamaños y código de Huffman de una tabla; devuelve dónde empieza lo siguiente
+    fn set_sizes(&self, d: &mut PairsData, mut data: usize) -> Option<usize> {
+        d.flags = self.byte(data)?;
+        data += 1;
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            d.min_sym_len = self.byte(data)?;
+            return Some(data + 1);
+        }
+
+        // El último factor de los grupos es el número de posiciones de la tabla
+        let groups = d.group_len.iter().position(|&len| len == 0).unwrap_or(MAX_PIECES);
+        let size = d.group_idx[groups];
+
+        d.block_size = 1 << self.byte(data)?;
+        d.span = 1 << self.byte(data + 1)?;
+        d.sparse_index_count = size.div_ceil(d.span as u64) as usize;
+        let padding = self.byte(data + 2)? as usize;
+        d.num_blocks = self.le32(data + 3) as usize;
+        d.block_length_count = d.num_blocks + padding;
+        let max_sym_len = self.byte(data + 7)? as usize;
+        d.min_sym_len = self.byte(data + 8)?;
+        data += 9;
+        if max_sym_len < d.min_sym_len as usize || max_sym_len > 32 {
+            return None;
+        }
+        d.lowest_sym = data;
+
+        // Código canónico: los símbolos más largos tienen valores más bajos, así que el primer
+        // símbolo de cada longitud, alineado a 64 bits, va decreciendo con la longitud
+        let lengths = max_sym_len - d.min_sym_len as usize + 1;
+        d.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = self.le16(d.lowest_sym + 2 * i) as u64;
+            let next_lowest = self.le16(d.lowest_sym + 2 * (i + 1)) as u64;
+            d.base64[i] = (d.base64[i + 1] + lowest).wrapping_sub(next_lowest) / 2;
+        }
+        for (i, base) in d.base64.iter_mut().enumerate() {
+            *base = base.checked_shl((64 - i - d.min_sym_len as usize) as u32).unwrap_or(0);
+        }
+        data += 2 * lengths;
+
+        let symbols = self.le16(data) as usize;
+        data += 2;
+        d.btree = data;
+        d.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                d.symlen[sym] = self.set_symlen(d, sym, &mut visited);
+            }
+        }
+        Some(data + 3 * symbols + (symbols & 1))
+    }
+
+    // Cada símbolo se expande en un par de símbolos hasta llegar a las hojas (Recursive Pairing)
+    fn set_symlen(&self, d: &mut PairsData, sym: usize, visited: &mut [bool]) -> u8 {
+        visited[sym] = true;
+        let (left, right) = self.btree(d, sym);
+        if right == 0xFFF || left >= visited.len() || right >= visited.len() {
+            return 0;
+        }
+        if !visited[left] {
+            d.symlen[left] = self.set_symlen(d, left, visited);
+        }
+        if !visited[right] {
+            d.symlen[right] = self.set_symlen(d, right, visited);
+        }
+        d.symlen[left].wrapping_add(d.symlen[right]).wrapping_add(1)
+    }
+
+    // Valor guardado en la posición `idx` de la tabla `d`
+    fn decompress(&self, d: &PairsData, idx: u64) -> Option<i32> {
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(d.min_sym_len as i32);
+        }
+
+        // El índice disperso da el bloque y la posición del valor en el centro de cada tramo de
+        // `span` valores; desde ahí se avanza o retrocede bloque a bloque
+        let k = (idx / d.span as u64) as usize;
+        if k >= d.sparse_index_count {
+            return None;
+        }
+        let mut block = self.le32(d.sparse_index + 6 * k) as i64;
+        let mut offset = self.le16(d.sparse_index + 6 * k + 4) as i64;
+        offset += (idx % d.span as u64) as i64 - (d.span / 2) as i64;
+
+        let block_length = |block: i64| self.le16(d.block_lengths + 2 * block as usize) as i64;
+        while offset < 0 {
+            block -= 1;
+            if block < 0 {
+                return None;
+            }
+            offset += block_length(block) + 1;
+        }
+        while offset > block_length(block) {
+            offset -= block_length(block) + 1;
+            block += 1;
+            if block as usize >= d.num_blocks {
+                return None;
+            }
+        }
+
+        // Se leen los símbolos del bloque hasta el que contiene el valor buscado
+        let min_len = d.min_sym_len as usize;
+        let mut ptr = d.data + block as usize * d.block_size;
+        let mut buf = self.be64(ptr);
+        ptr += 8;
+        let mut buf_size = 64;
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while buf < *d.base64.get(len)? {
+                len += 1;
+            }
+            sym = ((buf - d.base64[len]).checked_shr((64 - len - min_len) as u32).unwrap_or(0)) as usize;
+            sym += self.le16(d.lowest_sym + 2 * len) as usize;
+            let values = *d.symlen.get(sym)? as i64 + 1;
+            if offset < values {
+                break;
+            }
+            offset -= values;
+            len += min_len;
+            buf = buf.checked_shl(len as u32).unwrap_or(0);
+            buf_size -= len as i32;
+            if buf_size <= 32 {
+                buf_size += 32;
+                buf |= (self.be32(ptr) as u64) << (64 - buf_size);
+                ptr += 4;
+            }
+        }
+
+        // El símbolo representa varios valores seguidos: se baja por sus pares hasta la hoja
+        while d.symlen[sym] != 0 {
+            let (left, right) = self.btree(d, sym);
+            let left_values = *d.symlen.get(left)? as i64 + 1;
+            if offset < left_values {
+                sym = left;
+            } else {
+                offset -= left_values;
+                sym = right;
+            }
+            if sym >= d.symlen.len() {
+                return None;
+            }
+        }
+        Some(self.btree(d, sym).0 as i32)
+    }
+
+    // Índice de la posición en la tabla y su valor
+    fn probe(&self, entry: &TableEntry, position: &TbPieces, side_to_move: PieceColor, black_stronger: bool, wdl: Wdl) -> Result<i32, ProbeError> {
+        let tables = &*INDEX_TABLES;
+        let black_to_move = side_to_move == PieceColor::Black;
+        // Con material simétrico solo se guarda el turno de las blancas
+        let flip = black_stronger || (entry.symmetric && black_to_move);
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = (flip ^ black_to_move) as usize;
+
+        let mut squares = [0usize; MAX_PIECES];
+        let mut pieces = [0u8; MAX_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut file = 0;
+
+        // Con peones hay una tabla por columna del peón de referencia: el más cercano a la banda
+        // y, entre ellos, el más atrasado
+        if entry.has_pawns {
+            let lead_pawn = self.items[0][0].pieces[0] ^ flip_color;
+            for &(square, piece) in position.pieces.iter().filter(|&&(_, piece)| piece == lead_pawn) {
+                squares[size] = square ^ flip_squares;
+                pieces[size] = piece ^ flip_color;
+                size += 1;
+            }
+            lead_pawns = size;
+            let lead = (0..lead_pawns).max_by_key(|&i| tables.map_pawns[squares[i]]).unwrap_or(0);
+            squares.swap(0, lead);
+            file = cmp_file(squares[0]);
+        }
+
+        if self.kind == TableKind::Dtz {
+            let flags = self.items[0][file].flags;
+            // Las simétricas sin peones valen para los dos bandos
+            if (flags & FLAG_STM) as usize != stm && (entry.has_pawns || !entry.symmetric) {
+                return Err(ProbeError::ChangeStm);
+            }
+        }
+
+        let lead_pawn_code = if entry.has_pawns { self.items[0][0].pieces[0] ^ flip_color } else { 0 };
+        for &(square, piece) in &position.pieces {
+            if entry.has_pawns && piece == lead_pawn_code {
+                continue;
+            }
+            squares[size] = square ^ flip_squares;
+            pieces[size] = piece ^ flip_color;
+            size += 1;
+        }
+
+        let d = &self.items[if self.kind == TableKind::Wdl { stm } else { 0 }][file];
+
+        // Las piezas se colocan en el orden del archivo
+        for i in lead_pawns..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if d.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // Simetrías: la primera pieza en las columnas a-d...
+        if squares[0] % 8 > 3 {
+            for square in &mut squares[..size] {
+                *square ^= 7;
+            }
+        }
+
+        let mut idx: u64;
+        if entry.has_pawns {
+            idx = tables.lead_pawn_idx[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|&square| tables.map_pawns[square]);
+            for i in 1..lead_pawns {
+                idx += BINOMIAL[i][tables.map_pawns[squares[i]]];
+            }
+        } else {
+            // ...sin peones, además en las filas 1-4...
+            if squares[0] / 8 > 3 {
+                for square in &mut squares[..size] {
+                    *square ^= 56;
+                }
+            }
+            // ...y la primera del grupo inicial fuera de la diagonal a1-h8, por debajo de ella
+            for i in 0..d.group_len[0] {
+                let off = off_a1h8(squares[i]);
+                if off == 0 {
+                    continue;
+                }
+                if off > 0 {
+                    for square in &mut squares[i..size] {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            if entry.has_unique_pieces {
+                // Tres piezas distintas juntas (31332 combinaciones)
+                let (s0, s1, s2) = (squares[0], squares[1], squares[2]);
+                let adjust1 = (s1 > s0) as usize;
+                let adjust2 = (s2 > s0) as usize + (s2 > s1) as usize;
+                idx = if off_a1h8(s0) != 0 {
+                    ((tables.map_a1d1d4[s0] * 63 + (s1 - adjust1)) * 62 + s2 - adjust2) as u64
+                } else if off_a1h8(s1) != 0 {
+                    ((6 * 63 + (s0 / 8) * 28 + tables.map_b1h1h7[s1]) * 62 + s2 - adjust2) as u64
+                } else if off_a1h8(s2) != 0 {
+                    (6 * 63 * 62 + 4 * 28 * 62 + (s0 / 8) * 7 * 28 + (s1 / 8 - adjust1) * 28 + tables.map_b1h1h7[s2]) as u64
+                } else {
+                    (6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + (s0 / 8) * 7 * 6 + (s1 / 8 - adjust1) * 6 + (s2 / 8 - adjust2)) as u64
+                };
+            } else {
+                // Solo los dos reyes (462 combinaciones)
+                idx = tables.map_kk[tables.map_a1d1d4[squares[0]]][squares[1]] as u64;
+            }
+        }
+
+        // El resto de grupos, cada uno con sus casillas en orden y saltando las ya ocupadas
+        idx *= d.group_idx[0];
+        let mut start = d.group_len[0];
+        let mut remaining_pawns = entry.has_pawns && entry.pawn_count[1] > 0;
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let len = d.group_len[next];
+            squares[start..start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let square = squares[start + i];
+                let adjust = squares[..start].iter().filter(|&&s| square > s).count();
+                let free = square - adjust - if remaining_pawns { 8 } else { 0 };
+                n += BINOMIAL[i + 1][free];
+            }
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            start += len;
+            next += 1;
+        }
+
+        let value = self.decompress(d, idx).ok_or_else(|| ProbeError::Corrupt(entry.path(self.kind).to_path_buf()))?;
+        Ok(match self.kind {
+            TableKind::Wdl => value - 2,
+            TableKind::Dtz => self.map_dtz(file, value, wdl),
+        })
+    }
+
+    // Los valores DTZ se guardan numerados por frecuencia; el mapa del archivo los devuelve a
+    // distancias, que pueden estar en jugadas en lugar de medios movimientos
+    fn map_dtz(&self, file: usize, mut value: i32, wdl: Wdl) -> i32 {
+        const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+        let d = &self.items[0][file];
+        if d.flags & FLAG_MAPPED != 0 {
+            let start = d.map_idx[WDL_MAP[(wdl as i32 + 2) as usize]] as usize + value as usize;
+            value = if d.flags & FLAG_WIDE != 0 {
+                self.le16(self.dtz_map + 2 * start) as i32
+            } else {
+                self.byte(self.dtz_map + start).unwrap_or(0) as i32
+            };
+        }
+        let in_moves = match wdl {
+            Wdl::Win => d.flags & FLAG_WIN_PLIES == 0,
+            Wdl::Loss => d.flags & FLAG_LOSS_PLIES == 0,
+            Wdl::CursedWin | Wdl::BlessedLoss => true,
+            Wdl::Draw => false,
+        };
+        if in_moves {
+            value *= 2;
+        }
+        value + 1
+    }
+}
+
+// Agrupa las piezas que se codifican juntas (las iguales del mismo color, y al principio los
+// peones de referencia o las tres primeras piezas o los dos reyes) y calcula el factor de cada
+// grupo en el índice según el orden que indica el archivo
+fn set_groups(entry: &TableEntry, d: &mut PairsData, order: [u8; 2], file: usize) {
+    let mut n = 0;
+    let mut first_len: i32 = if entry.has_pawns { 0 } else if entry.has_unique_pieces { 3 } else { 2 };
+    d.group_len[0] = 1;
+    for i in 1..entry.piece_count {
+        first_len -= 1;
+        if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+            d.group_len[n] += 1;
+        } else {
+            n += 1;
+            d.group_len[n] = 1;
+        }
+    }
+    n += 1;
+    d.group_len[n] = 0;
+
+    let both_have_pawns = entry.has_pawns && entry.pawn_count[1] > 0;
+    let mut next = if both_have_pawns { 2 } else { 1 };
+    let mut free_squares = 64 - d.group_len[0] - if both_have_pawns { d.group_len[1] } else { 0 };
+    let mut idx: u64 = 1;
+    let mut k = 0;
+    while next < n || k == order[0] || k == order[1] {
+        if k == order[0] {
+            d.group_idx[0] = idx;
+            idx *= if entry.has_pawns {
+                INDEX_TABLES.lead_pawns_size[d.group_len[0]][file]
+            } else if entry.has_unique_pieces {
+                31332
+            } else {
+                462
+            };
+        } else if k == order[1] {
+            d.group_idx[1] = idx;
+            idx *= BINOMIAL[d.group_len[1]][48 - d.group_len[0]];
+        } else {
+            d.group_idx[next] = idx;
+            idx *= BINOMIAL[d.group_len[next]][free_squares];
+            free_squares -= d.group_len[next];
+            next += 1;
+        }
+        k += 1;
+    }
+    d.group_idx[n] = idx;
+}
+
+// Distancia de la columna a la banda (a y h -> 0, d y e -> 3)
+fn cmp_file(square: usize) -> usize {
+    let file = square % 8;
+    file.min(7 - file)
+}
+
+// Posición respecto a la diagonal a1-h8: 0 en ella, negativa por debajo
+fn off_a1h8(square: usize) -> i32 {
+    (square / 8) as i32 - (square % 8) as i32
+}
+
+// Combinaciones de k elementos entre n, para k hasta 6
+const BINOMIAL: [[u64; 64]; 7] = binomial_table();
+
+const fn binomial_table() -> [[u64; 64]; 7] {
+    let mut table = [[0; 64]; 7];
+    let mut n = 0;
+    while n < 64 {
+        table[0][n] = 1;
+        let mut k = 1;
+        while k < 7 && k <= n {
+            table[k][n] = table[k - 1][n - 1] + if k < n { table[k][n - 1] } else { 0 };
+            k += 1;
+        }
+        n += 1;
+    }
+    table
+}
+
+// Numeraciones de casillas con las que se indexan las posiciones
+struct IndexTables {
+    map_b1h1h7: [usize; 64], // Casillas bajo la diagonal a1-h8 -> 0..27
+    map_a1d1d4: [usize; 64], // Triángulo a1-d1-d4 -> 0..9 (la diagonal al final)
+    map_kk: [[usize; 64]; 10], // Las 462 posiciones legales de dos reyes
+    map_pawns: [usize; 64], // Casillas a2-h7 -> 0..47; el peón de referencia es el de valor mayor
+    lead_pawn_idx: [[u64; 64]; 6], // [peones de referencia][casilla del primero]
+    lead_pawns_size: [[u64; 4]; 6], // [peones de referencia][columna]
+}
+
+static INDEX_TABLES: LazyLock<IndexTables> = LazyLock::new(|| {
+    let mut tables = IndexTables {
+        map_b1h1h7: [0; 64],
+        map_a1d1d4: [0; 64],
+        map_kk: [[0; 64]; 10],
+        map_pawns: [0; 64],
+        lead_pawn_idx: [[0; 64]; 6],
+        lead_pawns_size: [[0; 4]; 6],
+    };
+
+    let mut code = 0;
+    for square in 0..64 {
+        if off_a1h8(square) < 0 {
+            tables.map_b1h1h7[square] = code;
+            code += 1;
+        }
+    }
+
+    let mut code = 0;
+    let mut diagonal = Vec::new();
+    for square in 0..=27 {
+        if off_a1h8(square) < 0 && square % 8 <= 3 {
+            tables.map_a1d1d4[square] = code;
+            code += 1;
+        } else if off_a1h8(square) == 0 && square % 8 <= 3 {
+            diagonal.push(square);
+        }
+    }
+    for square in diagonal {
+        tables.map_a1d1d4[square] = code;
+        code += 1;
+    }
+
+    // El primer rey en el triángulo; si está en la diagonal, el otro no puede quedar por encima
+    let mut code = 0;
+    let mut both_on_diagonal = Vec::new();
+    for idx in 0..10 {
+        for s1 in 0..=27 {
+            if tables.map_a1d1d4[s1] != idx || (idx == 0 && s1 != 1) {
+                continue;
+            }
+            for s2 in 0..64 {
+                let touching = (s1 % 8).abs_diff(s2 % 8) <= 1 && (s1 / 8).abs_diff(s2 / 8) <= 1;
+                if touching || (off_a1h8(s1) == 0 && off_a1h8(s2) > 0) {
+                    continue;
+                }
+                if off_a1h8(s1) == 0 && off_a1h8(s2) == 0 {
+                    both_on_diagonal.push((idx, s2));
+                } else {
+                    tables.map_kk[idx][s2] = code;
+                    code += 1;
+                }
+            }
+        }
+    }
+    for (idx, square) in both_on_diagonal {
+        tables.map_kk[idx][square] = code;
+        code += 1;
+    }
+
+    let mut available = 47;
+    for lead_pawns in 1..=5 {
+        for file in 0..4 {
+            let mut idx = 0;
+            for rank in 1..=6 {
+                let square = rank * 8 + file;
+                if lead_pawns == 1 {
+                    tables.map_pawns[square] = available;
+                    tables.map_pawns[square ^ 7] = available - 1;
+                    available = available.saturating_sub(2);
+                }
+                tables.lead_pawn_idx[lead_pawns][square] = idx;
+                idx += BINOMIAL[lead_pawns - 1][tables.map_pawns[square]];
+            }
+            tables.lead_pawns_size[lead_pawns][file] = idx;
+        }
+    }
+    tables
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{self, SearchLimits};
+    use crate::transposition::TranspositionTable;
+    use std::sync::atomic::AtomicBool;
+
+    // Directorio temporal con las tablas dadas (nombre y contenido)
+    fn table_dir(name: &str, files: &[(&str, &[u8])]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("ajedrez-syzygy-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (file, bytes) in files {
+            fs::write(dir.join(file), bytes).unwrap();
+        }
+        dir
+    }
+
+    // KQvK en la que todas las posiciones con blancas al turno son victoria y con negras derrota:
+    // cabecera, orden de los grupos, piezas (rey, dama y rey negro para cada bando) y los dos valores.
+    // Escrita a mano para probar las tablas de un solo valor y las capturas previas al sondeo; los
+    // valores reales los comprueban las pruebas con las tablas oficiales.
+    const KQVK_SINGLE_VALUE: &[u8] = &[
+        0x71, 0xE8, 0x23, 0x5D, 0x01, 0x00, 0x66, 0x55, 0xEE, 0x00, 0x80, 0x04, 0x80, 0x00,
+    ];
+
+    #[test]
+    fn missing_files() {
+        let tablebases = Tablebases::open("/no/existe/syzygy");
+        assert!(tablebases.is_empty());
+        assert_eq!(tablebases.max_pieces(), 0);
+
+        let mut board = Board::from_fen("8/8/8/4k3/8/8/8/KQ6 w - - 0 1").unwrap();
+        let history = [board.hash];
+        assert_eq!(tablebases.probe_wdl(&mut board), Ok(None));
+        assert_eq!(tablebases.rank_root_moves(&mut board, &history), Ok(None));
+    }
+
+    #[test]
+    fn corrupt_file() {
+        // Un archivo que no es una tabla no da valores, y cada sondeo que lo necesita lo dice
+        let dir = table_dir("danada", &[("KQvK.rtbw", b"no es una tabla")]);
+        let tablebases = Tablebases::open(dir.to_str().unwrap());
+        assert_eq!(tablebases.len(), 1);
+        let corrupt = Err(CorruptTable(dir.join("KQvK.rtbw")));
+        let mut board = Board::from_fen("8/8/8/4k3/8/8/8/KQ6 w - - 0 1").unwrap();
+        assert_eq!(tablebases.probe_wdl(&mut board), corrupt);
+        assert_eq!(tablebases.probe_wdl(&mut board), corrupt);
+        // Las posiciones de otro material no se ven afectadas
+        assert_eq!(tablebases.probe_wdl(&mut Board::from_fen("8/8/8/4k3/8/8/8/KR6 w - - 0 1").unwrap()), Ok(None));
+
+        // La búsqueda juega sin la tabla y avisa una vez
+        let limits = SearchLimits { depth: Some(3), ..SearchLimits::default() };
+        let mut errors = Vec::new();
+        let best = ai::search(&board, &[board.hash], &limits, Some(&tablebases), &mut TranspositionTable::new(1), &AtomicBool::new(false), |info| {
+            errors.extend_from_slice(&info.tb_errors);
+        });
+        assert!(best.is_some());
+        assert_eq!(errors, [CorruptTable(dir.join("KQvK.rtbw"))]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn single_value_table() {
+        let dir = table_dir("kqvk", &[("KQvK.rtbw", KQVK_SINGLE_VALUE)]);
+        let tablebases = Tablebases::open(dir.to_str().unwrap());
+        assert_eq!((tablebases.len(), tablebases.max_pieces()), (1, 3));
+
+        let probe = |fen: &str| tablebases.probe_wdl(&mut Board::from_fen(fen).unwrap()).unwrap();
+        assert_eq!(probe("8/8/8/4k3/8/8/8/KQ6 w - - 0 1"), Some(Wdl::Win));
+        assert_eq!(probe("8/8/8/4k3/8/8/8/KQ6 b - - 0 1"), Some(Wdl::Loss));
+        // Con los colores cambiados se usa la misma tabla
+        assert_eq!(probe("kq6/8/8/8/4K3/8/8/8 b - - 0 1"), Some(Wdl::Win));
+        // El rey blanco puede capturar la dama sin defensa: tablas
+        assert_eq!(probe("8/8/8/8/8/5k2/1q6/K7 w - - 0 1"), Some(Wdl::Draw));
+        // Material sin tabla o con más piezas de las que cubren
+        assert_eq!(probe("8/8/8/4k3/8/8/8/KR6 w - - 0 1"), None);
+        assert_eq!(probe("8/8/8/4k3/8/8/8/KQQ5 w - - 0 1"), None);
+
+        // Sin la tabla DTZ no se pueden ordenar las jugadas de la raíz
+        let mut board = Board::from_fen("8/8/8/4k3/8/8/8/KQ6 w - - 0 1").unwrap();
+        let history = [board.hash];
+        assert_eq!(tablebases.rank_root_moves(&mut board, &history), Ok(None));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    // KRvK completa (WDL y DTZ) en tests/syzygy. No es la del generador oficial: se obtuvo con un
+    // análisis retrógrado aparte y se escribió con el formato de Syzygy (Huffman sin pares y DTZ en
+    // medios movimientos, solo con blancas al turno). El lector coincide con ese análisis en todas
+    // las posiciones.
+    fn krvk() -> Tablebases {
+        let tablebases = Tablebases::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/syzygy"));
+        assert_eq!((tablebases.len(), tablebases.max_pieces()), (1, 3));
+        tablebases
+    }
+
+    #[test]
+    fn krvk_values() {
+        let tablebases = krvk();
+        let probe = |fen: &str| {
+            let mut board = Board::from_fen(fen).unwrap();
+            (tablebases.probe_wdl(&mut board).unwrap(), tablebases.probe_dtz(&mut board).unwrap())
+        };
+        // Mate en una, y el mate ya dado
+        assert_eq!(probe("k7/8/1K6/8/8/8/8/7R w - - 0 1"), (Some(Wdl::Win), Some(1)));
+        assert_eq!(probe("R6k/8/6K1/8/8/8/8/8 b - - 0 1"), (Some(Wdl::Loss), Some(-1)));
+        // El final más largo: 16 jugadas hasta el mate
+        assert_eq!(probe("8/8/8/8/8/2k5/1R6/K7 w - - 0 1"), (Some(Wdl::Win), Some(31)));
+        assert_eq!(probe("8/8/8/8/8/2k5/1R6/K7 b - - 0 1"), (Some(Wdl::Loss), Some(-32)));
+        // Ahogado, y la torre sin defensa
+        assert_eq!(probe("k7/1R6/1K6/8/8/8/8/8 b - - 0 1"), (Some(Wdl::Draw), Some(0)));
+        assert_eq!(probe("8/8/8/8/8/2k5/3R4/7K b - - 0 1"), (Some(Wdl::Draw), Some(0)));
+        // Con los colores cambiados se usa la misma tabla
+        assert_eq!(probe("k7/1r6/2K5/8/8/8/8/8 b - - 0 1"), (Some(Wdl::Win), Some(31)));
+        assert_eq!(probe("k7/1r6/2K5/8/8/8/8/8 w - - 0 1"), (Some(Wdl::Loss), Some(-32)));
+    }
+
+    #[test]
+    fn krvk_root_moves() {
+        let tablebases = krvk();
+        // Todas las jugadas de torre ganan, pero solo Th8 da mate
+        let mut board = Board::from_fen("k7/8/1K6/8/8/8/8/7R w - - 0 1").unwrap();
+        let history = [board.hash];
+        let ranked = tablebases.rank_root_moves(&mut board, &history).unwrap().unwrap();
+        let (best, _) = ranked.iter().max_by_key(|&&(_, rank)| rank).unwrap();
+        assert_eq!(best.to_string(), "h1h8");
+
+        // Jugando los dos bandos la mejor jugada según las tablas, cada una acorta la distancia
+        // en uno y el mate llega justo cuando decía la DTZ
+        let mut board = Board::from_fen("8/8/8/8/8/2k5/1R6/K7 w - - 0 1").unwrap();
+        let mut history = vec![board.hash];
+        let mut dtz = tablebases.probe_dtz(&mut board).unwrap().unwrap();
+        assert_eq!(dtz, 31);
+        while dtz != -1 {
+            let ranked = tablebases.rank_root_moves(&mut board, &history).unwrap().unwrap();
+            let &(best, _) = ranked.iter().max_by_key(|&&(_, rank)| rank).unwrap();
+            board.make_move(best);
+            history.push(board.hash);
+            let next = tablebases.probe_dtz(&mut board).unwrap().unwrap();
+            // El que recibe el mate tiene -1
+            let expected = match dtz {
+                1 => -1,
+                dtz if dtz > 0 => -(dtz - 1),
+                dtz => -dtz - 1,
+            };
+            assert_eq!(next, expected, "{}", board.to_fen());
+            dtz = next;
+        }
+        assert_eq!(history.len(), 32);
+        assert!(board.is_checkmate(board.side_to_move));
+    }
+
+    // Tablas oficiales de tests/syzygy/oficiales (ver el README de ese directorio)
+    fn official() -> Tablebases {
+        let tablebases = Tablebases::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/syzygy/oficiales"));
+        assert_eq!(tablebases.len(), 2, "faltan KRvK y KPvK en tests/syzygy/oficiales");
+        tablebases
+    }
+
+    // WDL y DTZ de una posición. Las tablas oficiales guardan la DTZ en jugadas, no en medios
+    // movimientos: el bando que gana la recibe exacta, pero la del que pierde puede pasarse en uno.
+    fn check_official(tablebases: &Tablebases, fen: &str, wdl: Wdl, dtz: i32) {
+        let mut board = Board::from_fen(fen).unwrap();
+        assert_eq!(tablebases.probe_wdl(&mut board), Ok(Some(wdl)), "{}", fen);
+        let probed = tablebases.probe_dtz(&mut board).unwrap().unwrap();
+        if dtz < 0 {
+            assert!(probed == dtz || probed == dtz - 1, "{}: {} en lugar de {}", fen, probed, dtz);
+        } else {
+            assert_eq!(probed, dtz, "{}", fen);
+        }
+    }
+
+    #[test]
+    #[ignore = "necesita las tablas oficiales KRvK en tests/syzygy/oficiales"]
+    fn official_krvk() {
+        let tablebases = official();
+        // Mate en una, y el mate ya dado
+        check_official(&tablebases, "k7/8/1K6/8/8/8/8/7R w - - 0 1", Wdl::Win, 1);
+        check_official(&tablebases, "R6k/8/6K1/8/8/8/8/8 b - - 0 1", Wdl::Loss, -1);
+        // Uno de los finales más largos: 16 jugadas hasta el mate
+        check_official(&tablebases, "8/8/8/8/8/2k5/1R6/K7 w - - 0 1", Wdl::Win, 31);
+        check_official(&tablebases, "8/8/8/8/8/2k5/1R6/K7 b - - 0 1", Wdl::Loss, -32);
+        // Ahogado, y la torre sin defensa
+        check_official(&tablebases, "k7/1R6/1K6/8/8/8/8/8 b - - 0 1", Wdl::Draw, 0);
+        check_official(&tablebases, "8/8/8/8/8/2k5/3R4/7K b - - 0 1", Wdl::Draw, 0);
+        // Con los colores cambiados
+        check_official(&tablebases, "k7/1r6/2K5/8/8/8/8/8 b - - 0 1", Wdl::Win, 31);
+
+        let mut board = Board::from_fen("k7/8/1K6/8/8/8/8/7R w - - 0 1").unwrap();
+        let history = [board.hash];
+        let ranked = tablebases.rank_root_moves(&mut board, &history).unwrap().unwrap();
+        let (best, _) = ranked.iter().max_by_key(|&&(_, rank)| rank).unwrap();
+        assert_eq!(best.to_string(), "h1h8");
+    }
+
+    #[test]
+    #[ignore = "necesita las tablas oficiales KPvK en tests/syzygy/oficiales"]
+    fn official_kpvk() {
+        let tablebases = official();
+        // El rey negro no alcanza el peón: avanzarlo ya gana
+        check_official(&tablebases, "8/8/8/8/8/8/P7/K6k w - - 0 1", Wdl::Win, 1);
+        check_official(&tablebases, "8/8/8/8/8/8/P7/K6k b - - 0 1", Wdl::Loss, -2);
+        // Con los colores cambiados se da la vuelta al tablero, también a los peones
+        check_official(&tablebases, "k6K/p7/8/8/8/8/8/8 b - - 0 1", Wdl::Win, 1);
+        // Peón de torre con el rey rival en la esquina
+        check_official(&tablebases, "k7/8/8/8/8/8/P7/K7 w - - 0 1", Wdl::Draw, 0);
+        // Rey en sexta delante del peón: gana mueva quien mueva
+        for fen in ["4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", "4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"] {
+            let mut board = Board::from_fen(fen).unwrap();
+            let white_wins = if board.side_to_move == PieceColor::White { Wdl::Win } else { Wdl::Loss };
+            assert_eq!(tablebases.probe_wdl(&mut board), Ok(Some(white_wins)), "{}", fen);
+        }
+        // Ahogado
+        check_official(&tablebases, "4k3/4P3/4K3/8/8/8/8/8 b - - 0 1", Wdl::Draw, 0);
+    }
+
+    #[test]
+    fn index_tables() {
+        let tables = &*INDEX_TABLES;
+        // Las 462 posiciones de dos reyes y las 31332 de tres piezas distintas
+        assert_eq!(tables.map_kk.iter().flatten().max(), Some(&461));
+        assert_eq!(BINOMIAL[2][5], 10);
+        assert_eq!(BINOMIAL[6][63], 67_945_521);
+        assert_eq!(tables.map_pawns[8], 47);
+        assert_eq!(tables.map_pawns[15], 46);
+        assert_eq!(tables.lead_pawns_size[1], [6, 6, 6, 6]);
+    }
+}
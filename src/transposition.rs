@@ -0,0 +1,183 @@
+use std::mem;
+use crate::moves::Move;
+
+// Tamaño por defecto de la tabla, en megabytes
+pub const DEFAULT_SIZE_MB: usize = 16;
+// Las puntuaciones a partir de aquí son mates y dependen de la distancia a la raíz
+const MATE_BOUND: i32 = crate::ai::MATE_THRESHOLD;
+
+// Qué indica la puntuación guardada respecto al valor real de la posición
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact, // Es el valor exacto
+    Lower, // El valor es como mínimo este (hubo un corte beta)
+    Upper, // El valor es como máximo este (ninguna jugada superó alfa)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    key: u64,
+    pub depth: i32,
+    pub score: i32, // Desde el punto de vista del bando que mueve en la posición
+    pub bound: Bound,
+    pub best_move: Option<Move>,
+    generation: u8, // Búsqueda en la que se guardó, para reemplazar antes lo antiguo
+}
+
+// Tabla de transposiciones de tamaño fijo indexada por el hash Zobrist de la posición
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+    generation: u8,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        let count = (size_mb.max(1) * 1024 * 1024 / mem::size_of::<Option<Entry>>()).max(1);
+        Self { entries: vec![None; count], generation: 0 }
+    }
+
+    // Cambia el tamaño de la tabla, vaciándola
+    pub fn resize(&mut self, size_mb: usize) {
+        *self = Self::new(size_mb);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+        self.generation = 0;
+    }
+
+    // Se llama al empezar cada búsqueda: lo guardado antes pasa a ser reemplazable
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key % self.entries.len() as u64) as usize
+    }
+
+    // Entrada de la posición, con los mates ya referidos a la raíz de la búsqueda actual
+    pub fn probe(&self, key: u64, ply: i32) -> Option<Entry> {
+        let mut entry = self.entries[self.index(key)].filter(|entry| entry.key == key)?;
+        entry.score = score_from_tt(entry.score, ply);
+        Some(entry)
+    }
+
+    // Guarda el resultado de buscar la posición a `depth`. Solo se conserva la entrada que
+    // ocupa el hueco si es de esta misma búsqueda y más profunda.
+    pub fn store(&mut self, key: u64, depth: i32, score: i32, bound: Bound, best_move: Option<Move>, ply: i32) {
+        let index = self.index(key);
+        let slot = &mut self.entries[index];
+        if let Some(old) = slot
+            && old.generation == self.generation
+            && old.depth > depth {
+            return;
+        }
+        // Si no hay mejor jugada nueva se mantiene la que hubiera de la misma posición
+        let best_move = best_move.or_else(|| slot.filter(|old| old.key == key).and_then(|old| old.best_move));
+        *slot = Some(Entry {
+            key,
+            depth,
+            score: score_to_tt(score, ply),
+            bound,
+            best_move,
+            generation: self.generation,
+        });
+    }
+
+    // Ocupación en tantos por mil, como la informa UCI ("hashfull")
+    pub fn hashfull(&self) -> usize {
+        let sample = self.entries.len().min(1000);
+        let used = self.entries[..sample]
+            .iter()
+            .filter(|entry| entry.is_some_and(|entry| entry.generation == self.generation))
+            .count();
+        used * 1000 / sample
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_SIZE_MB)
+    }
+}
+
+// En la búsqueda un mate vale MATE_SCORE menos su distancia a la raíz; en la tabla se guarda
+// su distancia a la posición, que es lo que vale igual desde cualquier otra raíz
+fn score_to_tt(score: i32, ply: i32) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply
+    } else if score <= -MATE_BOUND {
+        score - ply
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: i32) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply
+    } else if score <= -MATE_BOUND {
+        score + ply
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::MATE_SCORE;
+
+    #[test]
+    fn mate_scores_depend_on_ply() {
+        let mut tt = TranspositionTable::new(1);
+        // Mate en 3 medios movimientos desde un nodo a 5 de la raíz
+        tt.store(42, 4, MATE_SCORE - 8, Bound::Exact, None, 5);
+        // Desde otra raíz el mismo nodo está a 2: el mate está a 5 de ella
+        assert_eq!(tt.probe(42, 2).unwrap().score, MATE_SCORE - 5);
+        assert_eq!(tt.probe(42, 5).unwrap().score, MATE_SCORE - 8);
+
+        // Igual para recibir mate
+        tt.store(43, 4, -(MATE_SCORE - 7), Bound::Upper, None, 6);
+        assert_eq!(tt.probe(43, 1).unwrap().score, -(MATE_SCORE - 2));
+
+        // El resto de puntuaciones no cambian
+        tt.store(44, 4, 250, Bound::Lower, None, 9);
+        assert_eq!(tt.probe(44, 0).unwrap().score, 250);
+        assert!(tt.probe(45, 0).is_none());
+    }
+
+    #[test]
+    fn replacement() {
+        let mut tt = TranspositionTable::new(1);
+        let len = tt.entries.len() as u64;
+        // Dos posiciones que caen en el mismo hueco
+        let (a, b) = (7, 7 + len);
+
+        tt.store(a, 6, 10, Bound::Exact, None, 0);
+        tt.store(b, 3, 20, Bound::Exact, None, 0);
+        assert_eq!(tt.probe(a, 0).unwrap().depth, 6);
+        assert!(tt.probe(b, 0).is_none());
+
+        // A igual o mayor profundidad sí se reemplaza
+        tt.store(b, 6, 20, Bound::Exact, None, 0);
+        assert!(tt.probe(a, 0).is_none());
+        assert_eq!(tt.probe(b, 0).unwrap().score, 20);
+
+        // Lo de una búsqueda anterior se reemplaza aunque sea más profundo
+        tt.store(a, 9, 10, Bound::Exact, None, 0);
+        tt.new_search();
+        tt.store(b, 1, 30, Bound::Exact, None, 0);
+        assert!(tt.probe(a, 0).is_none());
+        assert_eq!(tt.probe(b, 0).unwrap().depth, 1);
+    }
+
+    #[test]
+    fn keeps_best_move() {
+        let mut tt = TranspositionTable::new(1);
+        let mv = Move::new((4, 6), (4, 4));
+        tt.store(1, 2, 0, Bound::Lower, Some(mv), 0);
+        tt.store(1, 3, 0, Bound::Upper, None, 0);
+        assert_eq!(tt.probe(1, 0).unwrap().best_move, Some(mv));
+    }
+}
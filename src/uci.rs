@@ -0,0 +1,294 @@
+use std::io::{self, BufRead};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use crate::ai::{self, SearchInfo, SearchLimits, MATE_SCORE, MATE_THRESHOLD};
+use crate::board::Board;
+use crate::moves::Move;
+use crate::syzygy::Tablebases;
+use crate::transposition::{self, TranspositionTable};
+
+// Tamaño máximo de la tabla de transposiciones que se puede pedir con "setoption name Hash"
+const MAX_HASH_MB: usize = 4096;
+
+// Bucle del protocolo UCI sobre la entrada y salida estándar (binario `chess-uci`)
+pub fn run() {
+    let mut board = Board::new();
+    let mut history = vec![board.hash];
+    let stop = Arc::new(AtomicBool::new(false));
+    // Compartida con el hilo de búsqueda, que la bloquea mientras busca
+    let tt = Arc::new(Mutex::new(TranspositionTable::default()));
+    // Se sustituyen enteras al cambiar "SyzygyPath"; la búsqueda en curso usa las suyas
+    let mut tablebases = Arc::new(Tablebases::empty());
+    let mut worker: Option<JoinHandle<()>> = None;
+
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first().copied() {
+            Some("uci") => {
+                println!("id name Ajedrez Rust {}", env!("CARGO_PKG_VERSION"));
+                println!("id author thedharex");
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    transposition::DEFAULT_SIZE_MB,
+                    MAX_HASH_MB,
+                );
+                println!("option name SyzygyPath type string default <empty>");
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("setoption") => {
+                stop_search(&stop, &mut worker);
+                if let Err(e) = set_option(&tokens[1..], &tt, &mut tablebases) {
+                    println!("info string {}", e);
+                }
+            }
+            Some("ucinewgame") => {
+                stop_search(&stop, &mut worker);
+                board = Board::new();
+                history = vec![board.hash];
+                tt.lock().unwrap().clear();
+            }
+            Some("position") => {
+                stop_search(&stop, &mut worker);
+                match parse_position(&tokens[1..]) {
+                    Ok((new_board, new_history)) => {
+                        board = new_board;
+                        history = new_history;
+                    }
+                    Err(e) => println!("info string {}", e),
+                }
+            }
+            Some("go") => {
+                stop_search(&stop, &mut worker);
+                stop.store(false, Ordering::Relaxed);
+                worker = Some(start_search(&board, &history, &tokens[1..], tt.clone(), tablebases.clone(), stop.clone()));
+            }
+            Some("stop") => stop_search(&stop, &mut worker),
+            Some("quit") => {
+                stop_search(&stop, &mut worker);
+                break;
+            }
+            // Las órdenes desconocidas se ignoran, como pide el protocolo
+            _ => {}
+        }
+    }
+    stop_search(&stop, &mut worker);
+}
+
+// Detiene la búsqueda en curso (si hay una) y espera a que envíe su "bestmove"
+fn stop_search(stop: &AtomicBool, worker: &mut Option<JoinHandle<()>>) {
+    if let Some(handle) = worker.take() {
+        stop.store(true, Ordering::Relaxed);
+        let _ = handle.join();
+    }
+}
+
+// "setoption name <nombre> [value <valor>]"
+fn set_option(tokens: &[&str], tt: &Mutex<TranspositionTable>, tablebases: &mut Arc<Tablebases>) -> Result<(), String> {
+    let value_at = tokens.iter().position(|&t| t == "value").unwrap_or(tokens.len());
+    let name = tokens.get(1..value_at).unwrap_or_default().join(" ");
+    let value = tokens.get(value_at + 1..).unwrap_or_default().join(" ");
+
+    match name.to_lowercase().as_str() {
+        "hash" => {
+            let size_mb = value.parse::<usize>().map_err(|_| format!("valor de Hash inválido '{}'", value))?;
+            tt.lock().unwrap().resize(size_mb.clamp(1, MAX_HASH_MB));
+            Ok(())
+        }
+        // Sin tablas en el directorio (o con "<empty>") el motor sigue jugando sin ellas
+        "syzygypath" => {
+            *tablebases = Arc::new(if value.is_empty() || value == "<empty>" {
+                Tablebases::empty()
+            } else {
+                Tablebases::open(&value)
+            });
+            println!("info string {} tablas de finales (hasta {} piezas)", tablebases.len(), tablebases.max_pieces());
+            Ok(())
+        }
+        _ => Err(format!("opción desconocida '{}'", name)),
+    }
+}
+
+// "position startpos|fen <FEN> [moves <m1> <m2> ...]"
+fn parse_position(tokens: &[&str]) -> Result<(Board, Vec<u64>), String> {
+    let moves_at = tokens.iter().position(|&t| t == "moves").unwrap_or(tokens.len());
+    let mut board = match tokens.first().copied() {
+        Some("startpos") => Board::new(),
+        Some("fen") => Board::from_fen(&tokens[1..moves_at].join(" ")).map_err(|e| e.to_string())?,
+        _ => return Err("se esperaba 'startpos' o 'fen'".to_string()),
+    };
+
+    let mut history = vec![board.hash];
+    for &text in tokens.iter().skip(moves_at + 1) {
+        let mv = parse_uci_move(&mut board, text).ok_or_else(|| format!("jugada ilegal '{}'", text))?;
+        board.make_move(mv);
+        history.push(board.hash);
+    }
+    Ok((board, history))
+}
+
+// Jugada legal en notación de coordenadas ("e2e4", "e7e8q", el enroque como "e1g1")
+fn parse_uci_move(board: &mut Board, text: &str) -> Option<Move> {
+    board.generate_legal_moves().into_iter().find(|mv| mv.to_string() == text)
+}
+
+// "go [depth N] [nodes N] [movetime MS] [wtime MS] [btime MS] [winc MS] [binc MS] [movestogo N] [infinite]"
+fn parse_go(tokens: &[&str]) -> SearchLimits {
+    let value = |name: &str| {
+        tokens.iter()
+            .position(|&t| t == name)
+            .and_then(|i| tokens.get(i + 1))
+            .and_then(|v| v.parse::<i64>().ok())
+    };
+    let millis = |name: &str| value(name).map(|ms| Duration::from_millis(ms.max(0) as u64));
+
+    SearchLimits {
+        depth: value("depth").map(|d| d.clamp(1, ai::MAX_DEPTH as i64) as i32),
+        movetime: millis("movetime"),
+        nodes: value("nodes").map(|n| n.max(1) as u64),
+        wtime: millis("wtime"),
+        btime: millis("btime"),
+        winc: millis("winc"),
+        binc: millis("binc"),
+        movestogo: value("movestogo").map(|n| n.max(1) as u32),
+        infinite: tokens.contains(&"infinite"),
+        random_ties: false,
+        eval_noise: 0,
+        move_margin: 0,
+    }
+}
+
+// Lanza la búsqueda de "go ..." en otro hilo para seguir atendiendo "stop" e "isready"
+fn start_search(
+    board: &Board,
+    history: &[u64],
+    tokens: &[&str],
+    tt: Arc<Mutex<TranspositionTable>>,
+    tablebases: Arc<Tablebases>,
+    stop: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    let limits = parse_go(tokens);
+    // "go infinite", o un "go" sin límites, busca hasta recibir "stop"
+    let wait_for_stop = limits.infinite || limits.is_unbounded(board.side_to_move);
+
+    let board = board.clone();
+    let history = history.to_vec();
+    thread::spawn(move || {
+        let best = ai::search(&board, &history, &limits, Some(&tablebases), &mut tt.lock().unwrap(), &stop, print_info);
+
+        // En modo infinito el "bestmove" solo se envía tras "stop"
+        while wait_for_stop && !stop.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(5));
+        }
+        match best {
+            Some(mv) => println!("bestmove {}", mv),
+            None => println!("bestmove 0000"),
+        }
+    })
+}
+
+fn print_info(info: &SearchInfo) {
+    for error in &info.tb_errors {
+        println!("info string {}", error);
+    }
+    let millis = info.elapsed.as_millis().max(1);
+    let nps = info.nodes as u128 * 1000 / millis;
+    let pv: Vec<String> = info.pv.iter().map(Move::to_string).collect();
+    println!(
+        "info depth {} score {} nodes {} nps {} time {} hashfull {} tbhits {} pv {}",
+        info.depth,
+        format_score(info.score),
+        info.nodes,
+        nps,
+        info.elapsed.as_millis(),
+        info.hashfull,
+        info.tbhits,
+        pv.join(" "),
+    );
+}
+
+// "cp <centipeones>" o "mate <jugadas>" (negativo si el motor recibe mate)
+fn format_score(score: i32) -> String {
+    if score.abs() >= MATE_THRESHOLD {
+        let plies = MATE_SCORE - score.abs();
+        let moves = (plies + 1) / 2;
+        format!("mate {}", if score > 0 { moves } else { -moves })
+    } else {
+        format!("cp {}", score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(command: &str) -> Result<(Board, Vec<u64>), String> {
+        let tokens: Vec<&str> = command.split_whitespace().collect();
+        parse_position(&tokens)
+    }
+
+    fn go(command: &str) -> SearchLimits {
+        let tokens: Vec<&str> = command.split_whitespace().collect();
+        parse_go(&tokens)
+    }
+
+    #[test]
+    fn position_commands() {
+        let (board, history) = position("startpos moves e2e4 e7e5").unwrap();
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.last(), Some(&board.hash));
+
+        // Enroque en notación de coordenadas y coronación
+        let (board, _) = position("fen r3k3/6P1/8/8/8/8/8/R3K2R w KQq - 0 1 moves e1g1 e8c8 g7g8q").unwrap();
+        assert_eq!(board.to_fen(), "2kr2Q1/8/8/8/8/8/8/R4RK1 b - - 0 2");
+        let (board, _) = position("fen 8/4P3/8/8/8/k7/8/K7 w - - 0 1 moves e7e8q").unwrap();
+        assert_eq!(board.to_fen(), "4Q3/8/8/8/8/k7/8/K7 b - - 0 1");
+
+        assert!(position("startpos moves e2e5").unwrap_err().contains("e2e5"));
+        assert!(position("fen 8/8/8 w - - 0 1").is_err());
+        assert!(position("moves e2e4").is_err());
+    }
+
+    #[test]
+    fn go_limits() {
+        let limits = go("wtime 60000 btime 55000 winc 1000 binc 500 movestogo 20 depth 12 nodes 5000 movetime 250");
+        assert_eq!(limits.wtime, Some(Duration::from_millis(60_000)));
+        assert_eq!(limits.btime, Some(Duration::from_millis(55_000)));
+        assert_eq!(limits.winc, Some(Duration::from_millis(1_000)));
+        assert_eq!(limits.binc, Some(Duration::from_millis(500)));
+        assert_eq!(limits.movestogo, Some(20));
+        assert_eq!(limits.depth, Some(12));
+        assert_eq!(limits.nodes, Some(5_000));
+        assert_eq!(limits.movetime, Some(Duration::from_millis(250)));
+        assert!(!limits.infinite);
+        assert!(!limits.random_ties);
+        assert_eq!((limits.eval_noise, limits.move_margin), (0, 0));
+
+        let limits = go("infinite");
+        assert!(limits.infinite);
+        assert_eq!((limits.depth, limits.wtime, limits.nodes), (None, None, None));
+
+        // Valores fuera de rango se ajustan; los que no son números se ignoran
+        let limits = go("depth 1000 wtime -5 nodes x");
+        assert_eq!(limits.depth, Some(ai::MAX_DEPTH));
+        assert_eq!(limits.wtime, Some(Duration::ZERO));
+        assert_eq!(limits.nodes, None);
+    }
+
+    #[test]
+    fn scores() {
+        assert_eq!(format_score(35), "cp 35");
+        assert_eq!(format_score(-120), "cp -120");
+        // Mate en el siguiente medio movimiento propio: "mate 1"; recibir mate en 2 medios: "mate -1"
+        assert_eq!(format_score(MATE_SCORE - 1), "mate 1");
+        assert_eq!(format_score(MATE_SCORE - 3), "mate 2");
+        assert_eq!(format_score(-(MATE_SCORE - 2)), "mate -1");
+        assert_eq!(format_score(-(MATE_SCORE - 4)), "mate -2");
+    }
+}
@@ -0,0 +1,84 @@
+use crate::pieces::{PieceColor, PieceType};
+
+// Claves aleatorias fijas para el hash Zobrist, generadas en compilación con splitmix64
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+struct Keys {
+    pieces: [[[u64; 64]; 6]; 2],
+    castling: [u64; 16],
+    en_passant: [u64; 8],
+    side: u64,
+}
+
+const fn generate_keys() -> Keys {
+    let mut keys = Keys {
+        pieces: [[[0; 64]; 6]; 2],
+        castling: [0; 16],
+        en_passant: [0; 8],
+        side: 0,
+    };
+    let mut state = 0x2545_F491_4F6C_DD1D;
+
+    let mut color = 0;
+    while color < 2 {
+        let mut piece = 0;
+        while piece < 6 {
+            let mut square = 0;
+            while square < 64 {
+                let (next, key) = splitmix64(state);
+                state = next;
+                keys.pieces[color][piece][square] = key;
+                square += 1;
+            }
+            piece += 1;
+        }
+        color += 1;
+    }
+
+    let mut i = 0;
+    while i < 16 {
+        let (next, key) = splitmix64(state);
+        state = next;
+        keys.castling[i] = key;
+        i += 1;
+    }
+
+    let mut file = 0;
+    while file < 8 {
+        let (next, key) = splitmix64(state);
+        state = next;
+        keys.en_passant[file] = key;
+        file += 1;
+    }
+
+    let (_, side) = splitmix64(state);
+    keys.side = side;
+    keys
+}
+
+static KEYS: Keys = generate_keys();
+
+// `square` es el índice y * 8 + x de la casilla
+pub fn piece_key(color: PieceColor, piece_type: PieceType, square: usize) -> u64 {
+    KEYS.pieces[color as usize][piece_type as usize][square]
+}
+
+// Índice de 4 bits con los derechos de enroque
+pub fn castling_key(rights: usize) -> u64 {
+    KEYS.castling[rights]
+}
+
+pub fn en_passant_key(file: usize) -> u64 {
+    KEYS.en_passant[file]
+}
+
+// Se aplica cuando le toca mover a las Negras
+pub fn side_key() -> u64 {
+    KEYS.side
+}